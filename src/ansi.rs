@@ -0,0 +1,232 @@
+//! ANSI escape sequences in window output.
+//!
+//! `Window::write` runs output through an `AnsiParser`. When the window has
+//! the interpreter turned on with `Window::set_ansi`, SGR sequences become
+//! styles, clear-line and moving the cursor within the line are applied, and
+//! other sequences are dropped. Carriage returns and newlines are always
+//! handled, so a line can be rewritten in place like a progress bar.
+
+use unicode_width::UnicodeWidthChar;
+
+use {Style, StyledText, ThemeColor};
+
+#[derive(Clone, Debug, Default)]
+pub struct AnsiParser {
+    escapes: bool,
+    style: Style,
+    // The unfinished line, one character per cell, and the cursor in it. The
+    // cell to the right of a wide character is empty.
+    cells: Vec<(String, Style)>,
+    col: usize,
+    // An escape sequence cut off at the end of the previous input
+    pending: String,
+}
+impl AnsiParser {
+    /// A parser that interprets escape sequences if `escapes` is true.
+    pub fn new(escapes: bool) -> AnsiParser {
+        AnsiParser {
+            escapes,
+            ..AnsiParser::default()
+        }
+    }
+    pub fn set_escapes(&mut self, escapes: bool) {
+        self.escapes = escapes;
+    }
+    pub fn escapes(&self) -> bool {
+        self.escapes
+    }
+    /// Interprets `s` and returns the lines it finished.
+    pub fn feed(&mut self, s: &str) -> Vec<StyledText> {
+        let input = self.pending.split_off(0) + s;
+        let mut lines = vec![];
+        let mut chars = input.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\n' => lines.push(self.finish_line()),
+                '\r' => self.col = 0,
+                '\x08' => self.col = self.col.saturating_sub(1),
+                '\t' => {
+                    let next = (self.col / 8 + 1) * 8;
+                    while self.col < next {
+                        self.put(" ");
+                    }
+                }
+                '\x1b' if self.escapes => {
+                    match sequence_end(&input[i..]) {
+                        Some(len) => {
+                            self.escape(&input[i..i + len]);
+                            while chars.peek().is_some_and(|&(j, _)| j < i + len) {
+                                chars.next();
+                            }
+                        }
+                        None => {
+                            self.pending = input[i..].to_owned();
+                            break;
+                        }
+                    }
+                }
+                c if c.is_control() => {}
+                c => {
+                    let mut buf = [0; 4];
+                    self.put(c.encode_utf8(&mut buf));
+                }
+            }
+        }
+        lines
+    }
+    /// The line that has not been finished with a newline yet.
+    pub fn line(&self) -> StyledText {
+        let mut text = StyledText::new();
+        for &(ref c, style) in self.cells.iter() {
+            text.push(c, style);
+        }
+        text
+    }
+    /// Drops the unfinished line.
+    pub fn clear_line(&mut self) {
+        self.cells.clear();
+        self.col = 0;
+    }
+    fn finish_line(&mut self) -> StyledText {
+        let line = self.line();
+        self.clear_line();
+        line
+    }
+    fn put(&mut self, c: &str) {
+        let width = c.chars().next().and_then(|c| c.width()).unwrap_or(0).min(2);
+        // Combining characters go to the character before the cursor
+        if width == 0 {
+            let col = self.col.min(self.cells.len());
+            let before = self.cells[..col]
+                .iter_mut()
+                .rev()
+                .find(|cell| !cell.0.is_empty());
+            if let Some(cell) = before {
+                cell.0.push_str(c);
+            }
+            return;
+        }
+        let end = self.col + width;
+        while self.cells.len() < end {
+            self.cells.push((" ".into(), Style::default()));
+        }
+        // Overwriting half of a wide character erases the other half
+        self.split_wide(self.col);
+        self.split_wide(end);
+        self.cells[self.col] = (c.to_owned(), self.style);
+        for cell in self.cells[self.col + 1..end].iter_mut() {
+            *cell = (String::new(), self.style);
+        }
+        self.col = end;
+    }
+    /// Replaces a wide character that the boundary `col` cuts in half with
+    /// a blank.
+    fn split_wide(&mut self, col: usize) {
+        if col > 0 && self.cells.get(col).is_some_and(|cell| cell.0.is_empty()) {
+            self.cells[col - 1] = (" ".into(), Style::default());
+            self.cells[col] = (" ".into(), Style::default());
+        }
+    }
+    fn escape(&mut self, seq: &str) {
+        if !seq.starts_with("\x1b[") {
+            return;
+        }
+        let params = &seq[2..seq.len() - 1];
+        let numbers = params.split(';')
+            .map(|p| p.parse::<usize>().unwrap_or(0))
+            .collect::<Vec<_>>();
+        let n = numbers.first().cloned().unwrap_or(0);
+        match seq.chars().last() {
+            Some('m') => self.sgr(&numbers),
+            Some('K') => {
+                match n {
+                    0 => {
+                        self.split_wide(self.col);
+                        self.cells.truncate(self.col);
+                    }
+                    1 => {
+                        self.split_wide(self.col + 1);
+                        for cell in self.cells.iter_mut().take(self.col + 1) {
+                            *cell = (" ".into(), Style::default());
+                        }
+                    }
+                    _ => self.cells.clear(),
+                }
+            }
+            Some('C') => self.col += n.max(1),
+            Some('D') => self.col = self.col.saturating_sub(n.max(1)),
+            Some('G') => self.col = n.max(1) - 1,
+            _ => {}
+        }
+    }
+    fn sgr(&mut self, params: &[usize]) {
+        let mut params = params.iter().cloned();
+        while let Some(p) = params.next() {
+            let style = &mut self.style;
+            match p {
+                0 => *style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dim = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.reverse = true,
+                22 => {
+                    style.bold = false;
+                    style.dim = false;
+                }
+                23 => style.italic = false,
+                24 => style.underline = false,
+                27 => style.reverse = false,
+                30..=37 => style.fg = Some(ThemeColor::Indexed((p - 30) as u8)),
+                38 => style.fg = extended_color(&mut params),
+                39 => style.fg = None,
+                40..=47 => style.bg = Some(ThemeColor::Indexed((p - 40) as u8)),
+                48 => style.bg = extended_color(&mut params),
+                49 => style.bg = None,
+                90..=97 => style.fg = Some(ThemeColor::Indexed((p - 90 + 8) as u8)),
+                100..=107 => style.bg = Some(ThemeColor::Indexed((p - 100 + 8) as u8)),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the rest of a `38;5;n` or `38;2;r;g;b` color.
+fn extended_color<I>(params: &mut I) -> Option<ThemeColor>
+    where I: Iterator<Item = usize>
+{
+    match params.next() {
+        Some(5) => params.next().map(|n| ThemeColor::Indexed(n as u8)),
+        Some(2) => {
+            let r = params.next()?;
+            let g = params.next()?;
+            let b = params.next()?;
+            Some(ThemeColor::Rgb(r as u8, g as u8, b as u8))
+        }
+        _ => None,
+    }
+}
+
+/// Length in bytes of the escape sequence at the start of `s`, or `None` if
+/// it is not complete.
+fn sequence_end(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    match bytes.get(1) {
+        None => None,
+        // CSI ends with a byte in the range @ to ~
+        Some(&b'[') => {
+            bytes[2..].iter().position(|&b| (0x40..=0x7e).contains(&b)).map(|i| i + 3)
+        }
+        // OSC ends with BEL or ST
+        Some(&b']') => {
+            let rest = &s[2..];
+            match (rest.find('\x07'), rest.find("\x1b\\")) {
+                (Some(bel), Some(st)) if st < bel => Some(st + 4),
+                (Some(bel), _) => Some(bel + 3),
+                (None, Some(st)) => Some(st + 4),
+                (None, None) => None,
+            }
+        }
+        Some(_) => Some(1 + s[1..].chars().next().unwrap().len_utf8()),
+    }
+}
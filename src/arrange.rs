@@ -0,0 +1,191 @@
+//! Moving and swapping windows in the tree.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use {CursesError, Direction, Id, WindowContainer, WindowPayload, WindowSplitDirection};
+
+impl WindowContainer {
+    /// Moves the focused tiled window in `direction`. The window swaps places
+    /// with its sibling, enters a neighbouring container, or leaves its
+    /// container for the nearest one above it that is split the right way.
+    pub fn move_focused(&mut self, direction: Direction) {
+        if self.floating_focused || self.fullscreen.is_some() || self.window_count() < 2 {
+            return;
+        }
+        let id = self.with_focused_container(|f| f.focused_window().borrow().id);
+        let path = self.path_to(id).unwrap();
+        let forward = direction == Direction::Right || direction == Direction::Down;
+        let (&index, parents) = path.split_last().unwrap();
+
+        // Within the container of the window
+        let next = self.with_container_at(parents, |c| {
+            let next = if forward {
+                index + 1
+            } else {
+                index.wrapping_sub(1)
+            };
+            if c.direction.direction_ok(direction) && next < c.payload.len() {
+                Some(next)
+            } else {
+                None
+            }
+        });
+        if let Some(next) = next {
+            let sibling = self.with_container_at(parents, |c| c.payload[next].clone());
+            match sibling {
+                WindowPayload::Container(ref target) => {
+                    let win = self.remove_path(&path);
+                    let mut target = target.borrow_mut();
+                    let at = if forward { 0 } else { target.payload.len() };
+                    target.insert_payload(at, win);
+                }
+                WindowPayload::Window(_) => {
+                    self.with_container_at(parents, |c| swap_payload(c, index, next));
+                }
+            }
+            self.focus_moved(id);
+            return;
+        }
+
+        // Out of the container, next to the subtree that held the window
+        for level in (0..parents.len()).rev() {
+            let ancestor = &path[..level];
+            if self.with_container_at(ancestor, |c| c.direction.direction_ok(direction)) {
+                let before = self.with_container_at(ancestor, |c| c.payload.len());
+                let win = self.remove_path(&path);
+                let after = self.with_container_at(ancestor, |c| c.payload.len());
+                let mut at = path[level];
+                // The subtree is gone if the window was all that was left in it
+                if forward && after == before {
+                    at += 1;
+                }
+                self.with_container_at(ancestor, |c| c.insert_payload(at, win));
+                self.focus_moved(id);
+                return;
+            }
+        }
+
+        // Already at the edge of the screen
+        if self.direction.direction_ok(direction) {
+            return;
+        }
+        self.wrap_root(direction);
+        let path = self.path_to(id).unwrap();
+        let win = self.remove_path(&path);
+        let at = if forward { self.payload.len() } else { 0 };
+        self.insert_payload(at, win);
+        self.focus_moved(id);
+    }
+    /// Swaps the places of the focused tiled window and the tiled window `id`.
+    /// The sizes of the places stay the same.
+    pub fn swap_with(&mut self, id: Id) -> Result<(), CursesError> {
+        if self.floating_focused || self.fullscreen.is_some() {
+            return Ok(());
+        }
+        let focused = self.with_focused_container(|f| f.focused_window().borrow().id);
+        let a = self.path_to(focused).unwrap();
+        let b = self.path_to(id).ok_or(CursesError::WindowNotFound(id))?;
+        if a == b {
+            return Ok(());
+        }
+        let (&ai, a_parents) = a.split_last().unwrap();
+        let (&bi, b_parents) = b.split_last().unwrap();
+        let first = self.with_container_at(a_parents, |c| c.payload[ai].clone());
+        let second = self.with_container_at(b_parents, |c| c.payload[bi].clone());
+        let (first_weight, second_weight) = (first.weight(), second.weight());
+        first.set_weight(second_weight);
+        second.set_weight(first_weight);
+        self.with_container_at(a_parents, |c| c.payload[ai] = second);
+        self.with_container_at(b_parents, |c| c.payload[bi] = first);
+        self.focus_moved(focused);
+        Ok(())
+    }
+    /// Indices of the payload entries from the root to the tiled window `id`.
+    fn path_to(&self, id: Id) -> Option<Vec<usize>> {
+        for (i, pl) in self.payload.iter().enumerate() {
+            match *pl {
+                WindowPayload::Window(ref w) => {
+                    if w.borrow().id == id {
+                        return Some(vec![i]);
+                    }
+                }
+                WindowPayload::Container(ref c) => {
+                    if let Some(mut path) = c.borrow().path_to(id) {
+                        path.insert(0, i);
+                        return Some(path);
+                    }
+                }
+            }
+        }
+        None
+    }
+    fn with_container_at<F, T>(&mut self, path: &[usize], f: F) -> T
+        where F: FnOnce(&mut WindowContainer) -> T
+    {
+        match path.split_first() {
+            None => f(self),
+            Some((&i, rest)) => {
+                let c = self.payload[i].as_container();
+                let mut c = c.borrow_mut();
+                c.with_container_at(rest, f)
+            }
+        }
+    }
+    /// Removes the payload at `path` along with the containers it leaves
+    /// empty.
+    fn remove_path(&mut self, path: &[usize]) -> WindowPayload {
+        let (&last, parents) = path.split_last().unwrap();
+        let removed = self.with_container_at(parents, |c| c.remove_payload(last));
+        for level in (0..parents.len()).rev() {
+            if !self.with_container_at(&parents[..=level], |c| c.payload.is_empty()) {
+                break;
+            }
+            self.with_container_at(&parents[..level], |c| c.remove_payload(parents[level]));
+        }
+        removed
+    }
+    fn remove_payload(&mut self, index: usize) -> WindowPayload {
+        let removed = self.payload.remove(index);
+        if self.focus > index || self.focus == self.payload.len() && self.focus > 0 {
+            self.focus -= 1;
+        }
+        removed
+    }
+    /// Inserts with an average sized slot, like new windows get.
+    fn insert_payload(&mut self, index: usize, pl: WindowPayload) {
+        let sum = self.payload.iter().map(|pl| pl.weight()).sum::<f64>();
+        if !self.payload.is_empty() {
+            pl.set_weight(sum / self.payload.len() as f64);
+        }
+        self.payload.insert(index, pl);
+        self.focus = index;
+    }
+    /// Moves everything in the root into a new container and splits the root
+    /// along `direction`.
+    fn wrap_root(&mut self, direction: Direction) {
+        let mut inner = WindowContainer::with_payload(self.backend.clone(),
+                                                      self.payload.split_off(0));
+        inner.direction = self.direction;
+        inner.focus = self.focus;
+        self.payload = vec![WindowPayload::Container(Rc::new(RefCell::new(inner)))];
+        self.focus = 0;
+        self.direction = match direction {
+            Direction::Left | Direction::Right => WindowSplitDirection::Vertical,
+            Direction::Up | Direction::Down => WindowSplitDirection::Horizontal,
+        };
+    }
+    fn focus_moved(&mut self, id: Id) {
+        self.prune();
+        self.focus_path(id);
+        self.resize();
+    }
+}
+
+/// Swaps two entries of a container, keeping the sizes of the slots.
+fn swap_payload(c: &mut WindowContainer, a: usize, b: usize) {
+    let (wa, wb) = (c.payload[a].weight(), c.payload[b].weight());
+    c.payload.swap(a, b);
+    c.payload[a].set_weight(wa);
+    c.payload[b].set_weight(wb);
+}
@@ -0,0 +1,284 @@
+use ncurses::*;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{Backend, Color, MouseEvent, MouseKind, Surface};
+use {CursesError, Style, Theme, ThemeColor};
+
+static INIT: Once = Once::new();
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+// Pairs below this one are used for `Color`s
+const FIRST_STYLE_PAIR: i16 = 16;
+
+/// The ncurses backend. Only one can be alive at a time, but a new one can be
+/// created after the previous one has been dropped.
+pub struct CursesBackend {
+    windows: RefCell<Vec<Option<(WINDOW, i32)>>>,
+    theme: RefCell<Theme>,
+    // Color pairs made for styled text, by foreground and background
+    pairs: RefCell<HashMap<(i16, i16), i16>>,
+    // Palette entries changed to show RGB colors
+    palette: RefCell<Vec<PaletteEntry>>,
+}
+struct PaletteEntry {
+    rgb: (u8, u8, u8),
+    index: i16,
+    // The red, green and blue parts the entry had before
+    old: (i16, i16, i16),
+}
+impl CursesBackend {
+    pub fn new() -> Result<CursesBackend, CursesError> {
+        if ACTIVE.swap(true, Ordering::SeqCst) {
+            return Err(CursesError::CursesAlreadyInitialized);
+        }
+        let mut first = false;
+        INIT.call_once(|| {
+            first = true;
+            // Needed for printing UTF-8
+            setlocale(LcCategory::all, "");
+            initscr();
+            if !has_colors() {
+                panic!("No colors");
+            }
+            curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+            noecho();
+            refresh();
+            start_color();
+            use_default_colors();
+        });
+        if !first {
+            // Resumes the screen after endwin()
+            refresh();
+        }
+        let backend = CursesBackend {
+            windows: RefCell::new(vec![]),
+            theme: RefCell::new(Theme::default()),
+            pairs: RefCell::new(HashMap::new()),
+            palette: RefCell::new(vec![]),
+        };
+        backend.set_theme(&Theme::default());
+        Ok(backend)
+    }
+    /// The attributes for drawing text with `style`.
+    fn attributes(&self, style: Style) -> attr_t {
+        let text = self.theme.borrow().text;
+        let index = |c: Option<ThemeColor>, default: ThemeColor| {
+            self.color_index(c.unwrap_or(default))
+        };
+        let key = (index(style.fg, text.fg), index(style.bg, text.bg));
+        let mut pairs = self.pairs.borrow_mut();
+        let next = FIRST_STYLE_PAIR + pairs.len() as i16;
+        let pair = match pairs.get(&key) {
+            Some(&pair) => pair,
+            None if (next as i32) < COLOR_PAIRS() => {
+                init_pair(next, key.0, key.1);
+                pairs.insert(key, next);
+                next
+            }
+            // Out of color pairs
+            None => Color::Default.into(),
+        };
+        let mut attr = COLOR_PAIR(pair);
+        for &(on, a) in [(style.bold, A_BOLD()),
+                         (style.dim, A_DIM()),
+                         (style.italic, A_ITALIC()),
+                         (style.underline, A_UNDERLINE()),
+                         (style.reverse, A_REVERSE())]
+            .iter() {
+            if on {
+                attr |= a;
+            }
+        }
+        attr
+    }
+    /// The palette entry that shows `color`. RGB colors get an entry of their
+    /// own if the terminal can change its palette.
+    fn color_index(&self, color: ThemeColor) -> i16 {
+        let colors = COLORS();
+        if let ThemeColor::Rgb(r, g, b) = color {
+            if can_change_color() {
+                if let Some(index) = self.rgb_index((r, g, b), colors) {
+                    return index;
+                }
+            }
+        }
+        color.palette_index(colors)
+    }
+    /// Sets a palette entry to `rgb`, taking the entries from the end of the
+    /// palette. Returns `None` once only the basic colors are left.
+    fn rgb_index(&self, rgb: (u8, u8, u8), colors: i32) -> Option<i16> {
+        let mut palette = self.palette.borrow_mut();
+        if let Some(entry) = palette.iter().find(|entry| entry.rgb == rgb) {
+            return Some(entry.index);
+        }
+        let index = colors.min(i16::MAX as i32) - 1 - palette.len() as i32;
+        if index < 16 {
+            return None;
+        }
+        let index = index as i16;
+        let mut old = (0, 0, 0);
+        color_content(index, &mut old.0, &mut old.1, &mut old.2);
+        // ncurses takes the parts in the range 0 to 1000
+        let part = |c: u8| (c as i32 * 1000 / 255) as i16;
+        init_color(index, part(rgb.0), part(rgb.1), part(rgb.2));
+        palette.push(PaletteEntry { rgb, index, old });
+        Some(index)
+    }
+    fn win(&self, surface: Surface) -> WINDOW {
+        self.windows.borrow()[surface.0].expect("Surface already destroyed").0
+    }
+}
+impl Backend for CursesBackend {
+    fn screen_size(&self) -> (i32, i32) {
+        let mut w = 0;
+        let mut h = 0;
+        getmaxyx(stdscr(), &mut h, &mut w);
+        (w, h)
+    }
+    fn create_surface(&self, x: i32, y: i32, width: i32, height: i32) -> Surface {
+        let win = newwin(height, width, y, x);
+        wbkgd(win, COLOR_PAIR(Color::Default.into()));
+        nodelay(win, true);
+        keypad(win, true);
+        let mut windows = self.windows.borrow_mut();
+        match windows.iter().position(|w| w.is_none()) {
+            Some(pos) => {
+                windows[pos] = Some((win, 0));
+                Surface(pos)
+            }
+            None => {
+                windows.push(Some((win, 0)));
+                Surface(windows.len() - 1)
+            }
+        }
+    }
+    fn destroy_surface(&self, surface: Surface) {
+        if let Some((win, _)) = self.windows.borrow_mut()[surface.0].take() {
+            delwin(win);
+        }
+    }
+    fn move_surface(&self, surface: Surface, x: i32, y: i32) {
+        mvwin(self.win(surface), y, x);
+    }
+    fn resize_surface(&self, surface: Surface, width: i32, height: i32) {
+        wresize(self.win(surface), height, width);
+    }
+    fn clear(&self, surface: Surface) {
+        wclear(self.win(surface));
+    }
+    fn clear_to_eol(&self, surface: Surface, x: i32, y: i32) {
+        let win = self.win(surface);
+        wmove(win, y, x);
+        wclrtoeol(win);
+    }
+    fn print(&self, surface: Surface, x: i32, y: i32, text: &str) {
+        mvwaddstr(self.win(surface), y, x, text);
+    }
+    fn print_styled(&self, surface: Surface, x: i32, y: i32, text: &str, style: Style) {
+        let win = self.win(surface);
+        wattrset(win, self.attributes(style));
+        mvwaddstr(win, y, x, text);
+        wattrset(win, A_NORMAL());
+    }
+    fn draw_header(&self, surface: Surface, margin: i32, text: &str, color: Color) {
+        let win = self.win(surface);
+        wbkgd(win, COLOR_PAIR(color.into()));
+        werase(win);
+        mvwaddstr(win, 0, margin, text);
+    }
+    fn draw_border(&self, surface: Surface) {
+        let win = self.win(surface);
+        wbkgd(win, COLOR_PAIR(Color::Border.into()));
+        mvwvline(win, 0, 0, ACS_HLINE(), 1000);
+    }
+    fn set_z_order(&self, surface: Surface, z: i32) {
+        if let Some(ref mut w) = self.windows.borrow_mut()[surface.0] {
+            w.1 = z;
+        }
+    }
+    fn refresh(&self, surface: Surface) {
+        let windows = self.windows.borrow();
+        let (win, z) = windows[surface.0].expect("Surface already destroyed");
+        wnoutrefresh(win);
+        let mut above = windows.iter().flatten().filter(|w| w.1 > z).collect::<Vec<_>>();
+        above.sort_by_key(|w| w.1);
+        for &&(win, _) in above.iter() {
+            touchwin(win);
+            wnoutrefresh(win);
+        }
+        doupdate();
+    }
+    fn get_key(&self, surface: Surface) -> i32 {
+        wgetch(self.win(surface))
+    }
+    fn enable_mouse(&self, enabled: bool) {
+        if enabled {
+            mousemask((ALL_MOUSE_EVENTS | REPORT_MOUSE_POSITION) as mmask_t, None);
+            // Report presses and releases right away instead of clicks
+            mouseinterval(0);
+        } else {
+            mousemask(0, None);
+        }
+    }
+    fn set_theme(&self, theme: &Theme) {
+        *self.theme.borrow_mut() = *theme;
+        for &color in [Color::Default,
+                       Color::Selection,
+                       Color::Status,
+                       Color::StatusSelected,
+                       Color::Border,
+                       Color::Urgent]
+            .iter() {
+            let pair = theme.pair(color);
+            init_pair(color.into(), self.color_index(pair.fg), self.color_index(pair.bg));
+        }
+    }
+    fn theme(&self) -> Theme {
+        *self.theme.borrow()
+    }
+    fn get_mouse(&self) -> Option<MouseEvent> {
+        let mut event = MEVENT {
+            id: 0,
+            x: 0,
+            y: 0,
+            z: 0,
+            bstate: 0,
+        };
+        if getmouse(&mut event) != OK {
+            return None;
+        }
+        let state = event.bstate as i64;
+        let kind = if state & BUTTON1_PRESSED as i64 != 0 {
+            MouseKind::Press
+        } else if state & BUTTON1_RELEASED as i64 != 0 {
+            MouseKind::Release
+        } else if state & REPORT_MOUSE_POSITION as i64 != 0 {
+            MouseKind::Drag
+        } else {
+            return None;
+        };
+        Some(MouseEvent {
+            x: event.x,
+            y: event.y,
+            kind,
+        })
+    }
+}
+impl Drop for CursesBackend {
+    fn drop(&mut self) {
+        for (win, _) in self.windows.borrow_mut().drain(..).flatten() {
+            delwin(win);
+        }
+        self.enable_mouse(false);
+        // Gives the terminal its own colors back
+        for entry in self.palette.borrow_mut().drain(..) {
+            let (r, g, b) = entry.old;
+            init_color(entry.index, r, g, b);
+        }
+        endwin();
+        ACTIVE.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,294 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{Backend, Color, MouseEvent, Surface};
+use {display_width, Style, Theme};
+
+struct HeadlessSurface {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    // One grapheme per cell. The cell to the right of a wide character is
+    // empty.
+    cells: Vec<Vec<String>>,
+    styles: Vec<Vec<Style>>,
+    color: Option<Color>,
+    z: i32,
+}
+impl HeadlessSurface {
+    fn blank<T: Clone>(width: i32, height: i32, value: T) -> Vec<Vec<T>> {
+        (0..height.max(0)).map(|_| vec![value.clone(); width.max(0) as usize]).collect()
+    }
+    /// Puts a grapheme that is `width` cells wide at `x`, `y`.
+    fn put(&mut self, x: i32, y: i32, g: &str, width: i32, style: Style) {
+        if x < 0 || y < 0 || x + width > self.width || y >= self.height {
+            return;
+        }
+        let row = &mut self.cells[y as usize];
+        let (start, end) = (x as usize, (x + width) as usize);
+        // Overwriting half of a wide character erases the other half
+        if start > 0 && row[start].is_empty() {
+            row[start - 1] = " ".into();
+        }
+        if row.get(end).is_some_and(|c| c.is_empty()) {
+            row[end] = " ".into();
+        }
+        for (i, cell) in row[start..end].iter_mut().enumerate() {
+            *cell = if i == 0 { g.into() } else { String::new() };
+        }
+        for style_cell in self.styles[y as usize][start..end].iter_mut() {
+            *style_cell = style;
+        }
+    }
+    fn clear(&mut self) {
+        self.cells = HeadlessSurface::blank(self.width, self.height, " ".into());
+        self.styles = HeadlessSurface::blank(self.width, self.height, Style::default());
+    }
+}
+
+/// Copies the cells of a grid into one of a new size.
+fn resized<T: Clone>(grid: &[Vec<T>], width: i32, height: i32, value: T) -> Vec<Vec<T>> {
+    let mut new = HeadlessSurface::blank(width, height, value);
+    for (y, row) in new.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            if let Some(old) = grid.get(y).and_then(|r| r.get(x)) {
+                *cell = old.clone();
+            }
+        }
+    }
+    new
+}
+
+/// A backend that draws into memory instead of a terminal.
+///
+/// Every surface keeps its own geometry and character grid, and refreshing a
+/// surface copies it onto a virtual screen the same way `wrefresh` does. This
+/// makes it possible to run tcwm without a terminal and inspect the results.
+pub struct HeadlessBackend {
+    size: RefCell<(i32, i32)>,
+    screen: RefCell<Vec<Vec<String>>>,
+    surfaces: RefCell<Vec<Option<HeadlessSurface>>>,
+    keys: RefCell<VecDeque<i32>>,
+    mouse: RefCell<VecDeque<MouseEvent>>,
+    mouse_enabled: Cell<bool>,
+    refreshed: RefCell<Vec<Surface>>,
+    theme: RefCell<Theme>,
+}
+impl HeadlessBackend {
+    pub fn new(width: i32, height: i32) -> HeadlessBackend {
+        HeadlessBackend {
+            size: RefCell::new((width, height)),
+            screen: RefCell::new(HeadlessSurface::blank(width, height, " ".to_owned())),
+            surfaces: RefCell::new(vec![]),
+            keys: RefCell::new(VecDeque::new()),
+            mouse: RefCell::new(VecDeque::new()),
+            mouse_enabled: Cell::new(false),
+            refreshed: RefCell::new(vec![]),
+            theme: RefCell::new(Theme::default()),
+        }
+    }
+    /// Changes the size of the virtual screen and queues a `RESIZE` key like
+    /// a real terminal would.
+    pub fn resize_screen(&self, width: i32, height: i32) {
+        *self.size.borrow_mut() = (width, height);
+        *self.screen.borrow_mut() = HeadlessSurface::blank(width, height, " ".to_owned());
+        self.push_key(::RESIZE);
+    }
+    /// Queues a key to be returned from `get_key`.
+    pub fn push_key(&self, key: i32) {
+        self.keys.borrow_mut().push_back(key);
+    }
+    /// Queues a mouse event along with the `KEY_MOUSE` key announcing it.
+    pub fn push_mouse(&self, event: MouseEvent) {
+        self.mouse.borrow_mut().push_back(event);
+        self.push_key(::ncurses::KEY_MOUSE);
+    }
+    /// Position and size of a surface as `(x, y, width, height)`.
+    pub fn geometry(&self, surface: Surface) -> Option<(i32, i32, i32, i32)> {
+        self.surfaces.borrow()[surface.0]
+            .as_ref()
+            .map(|s| (s.x, s.y, s.width, s.height))
+    }
+    /// Contents of a surface, one string per row.
+    pub fn contents(&self, surface: Surface) -> Option<Vec<String>> {
+        self.surfaces.borrow()[surface.0]
+            .as_ref()
+            .map(|s| s.cells.iter().map(|row| row.concat()).collect())
+    }
+    /// Style of every cell of a surface, one vector per row.
+    pub fn styles(&self, surface: Surface) -> Option<Vec<Vec<Style>>> {
+        self.surfaces.borrow()[surface.0].as_ref().map(|s| s.styles.clone())
+    }
+    /// The color a surface was last filled with.
+    pub fn color(&self, surface: Surface) -> Option<Color> {
+        self.surfaces.borrow()[surface.0].as_ref().and_then(|s| s.color)
+    }
+    /// Whether mouse events are reported, as set with `enable_mouse`.
+    pub fn mouse_enabled(&self) -> bool {
+        self.mouse_enabled.get()
+    }
+    /// Surfaces refreshed since the last call, in the order they were
+    /// refreshed.
+    pub fn take_refreshed(&self) -> Vec<Surface> {
+        self.refreshed.borrow_mut().drain(..).collect()
+    }
+    /// Number of surfaces that have not been destroyed.
+    pub fn surface_count(&self) -> usize {
+        self.surfaces.borrow().iter().filter(|s| s.is_some()).count()
+    }
+    /// The virtual screen as text, with trailing whitespace trimmed from
+    /// every row.
+    pub fn screen(&self) -> String {
+        self.screen
+            .borrow()
+            .iter()
+            .map(|row| row.concat().trim_end().to_owned())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+    fn with_surface<F>(&self, surface: Surface, f: F)
+        where F: FnOnce(&mut HeadlessSurface)
+    {
+        let mut surfaces = self.surfaces.borrow_mut();
+        f(surfaces[surface.0].as_mut().expect("Surface already destroyed"))
+    }
+    fn copy_to_screen(&self, s: &HeadlessSurface) {
+        let mut screen = self.screen.borrow_mut();
+        for (dy, row) in s.cells.iter().enumerate() {
+            let y = s.y + dy as i32;
+            if y < 0 || y as usize >= screen.len() {
+                continue;
+            }
+            let screen_row = &mut screen[y as usize];
+            for (dx, c) in row.iter().enumerate() {
+                let x = s.x + dx as i32;
+                if x >= 0 && (x as usize) < screen_row.len() {
+                    screen_row[x as usize] = c.clone();
+                }
+            }
+        }
+    }
+}
+impl Backend for HeadlessBackend {
+    fn screen_size(&self) -> (i32, i32) {
+        *self.size.borrow()
+    }
+    fn create_surface(&self, x: i32, y: i32, width: i32, height: i32) -> Surface {
+        let surface = HeadlessSurface {
+            x,
+            y,
+            width,
+            height,
+            cells: HeadlessSurface::blank(width, height, " ".to_owned()),
+            styles: HeadlessSurface::blank(width, height, Style::default()),
+            color: None,
+            z: 0,
+        };
+        let mut surfaces = self.surfaces.borrow_mut();
+        match surfaces.iter().position(|s| s.is_none()) {
+            Some(pos) => {
+                surfaces[pos] = Some(surface);
+                Surface(pos)
+            }
+            None => {
+                surfaces.push(Some(surface));
+                Surface(surfaces.len() - 1)
+            }
+        }
+    }
+    fn destroy_surface(&self, surface: Surface) {
+        self.surfaces.borrow_mut()[surface.0] = None;
+    }
+    fn move_surface(&self, surface: Surface, x: i32, y: i32) {
+        self.with_surface(surface, |s| {
+            s.x = x;
+            s.y = y;
+        })
+    }
+    fn resize_surface(&self, surface: Surface, width: i32, height: i32) {
+        self.with_surface(surface, |s| {
+            s.cells = resized(&s.cells, width, height, " ".to_owned());
+            s.styles = resized(&s.styles, width, height, Style::default());
+            s.width = width;
+            s.height = height;
+        })
+    }
+    fn clear(&self, surface: Surface) {
+        self.with_surface(surface, |s| s.clear())
+    }
+    fn clear_to_eol(&self, surface: Surface, x: i32, y: i32) {
+        self.with_surface(surface, |s| for col in x..s.width {
+            s.put(col, y, " ", 1, Style::default());
+        })
+    }
+    fn print(&self, surface: Surface, x: i32, y: i32, text: &str) {
+        self.print_styled(surface, x, y, text, Style::default());
+    }
+    fn print_styled(&self, surface: Surface, x: i32, y: i32, text: &str, style: Style) {
+        self.with_surface(surface, |s| {
+            // Long lines wrap to the next row like they do with ncurses
+            let (mut x, mut y) = (x, y);
+            for g in text.graphemes(true) {
+                let width = display_width(g) as i32;
+                if width == 0 {
+                    // Combining marks without a base join the previous cell
+                    if x > 0 && x <= s.width && y >= 0 && y < s.height {
+                        s.cells[y as usize][x as usize - 1].push_str(g);
+                    }
+                    continue;
+                }
+                if x + width > s.width {
+                    x = 0;
+                    y += 1;
+                }
+                s.put(x, y, g, width, style);
+                x += width;
+            }
+        })
+    }
+    fn draw_header(&self, surface: Surface, margin: i32, text: &str, color: Color) {
+        self.clear(surface);
+        self.with_surface(surface, |s| s.color = Some(color));
+        self.print(surface, margin, 0, text);
+    }
+    fn draw_border(&self, surface: Surface) {
+        self.with_surface(surface, |s| {
+            s.color = Some(Color::Border);
+            for y in 0..s.height {
+                s.put(0, y, "|", 1, Style::default());
+            }
+        })
+    }
+    fn set_z_order(&self, surface: Surface, z: i32) {
+        self.with_surface(surface, |s| s.z = z)
+    }
+    fn refresh(&self, surface: Surface) {
+        let surfaces = self.surfaces.borrow();
+        let s = surfaces[surface.0].as_ref().expect("Surface already destroyed");
+        self.refreshed.borrow_mut().push(surface);
+        self.copy_to_screen(s);
+        let mut above = surfaces.iter().flatten().filter(|o| o.z > s.z).collect::<Vec<_>>();
+        above.sort_by_key(|o| o.z);
+        for o in above {
+            self.copy_to_screen(o);
+        }
+    }
+    fn get_key(&self, _surface: Surface) -> i32 {
+        self.keys.borrow_mut().pop_front().unwrap_or(-1)
+    }
+    fn get_mouse(&self) -> Option<MouseEvent> {
+        self.mouse.borrow_mut().pop_front()
+    }
+    fn enable_mouse(&self, enabled: bool) {
+        self.mouse_enabled.set(enabled);
+    }
+    fn set_theme(&self, theme: &Theme) {
+        *self.theme.borrow_mut() = *theme;
+    }
+    fn theme(&self) -> Theme {
+        *self.theme.borrow()
+    }
+}
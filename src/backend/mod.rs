@@ -0,0 +1,89 @@
+//! Rendering backends.
+//!
+//! Everything tcwm draws goes through the `Backend` trait. A backend hands out
+//! `Surface`s, which are rectangular regions of the screen that can be moved,
+//! resized, drawn to and refreshed. Windows own a content surface, a header
+//! surface and optionally a border surface.
+
+use std::rc::Rc;
+
+use {Style, Theme};
+
+mod curses;
+mod headless;
+
+pub use self::curses::CursesBackend;
+pub use self::headless::HeadlessBackend;
+
+pub type BackendRef = Rc<dyn Backend>;
+
+/// Opaque handle to a surface created by a `Backend`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Surface(pub usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Color {
+    Default = 1,
+    Selection = 2,
+    Status = 3,
+    StatusSelected = 4,
+    Border = 5,
+    Urgent = 6,
+}
+/// What happened in a `MouseEvent`. Only the left button is reported.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MouseKind {
+    Press,
+    Release,
+    /// The mouse moved while the button was held down.
+    Drag,
+}
+
+/// A mouse event at screen coordinates `x` and `y`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub x: i32,
+    pub y: i32,
+    pub kind: MouseKind,
+}
+
+impl From<Color> for i16 {
+    fn from(color: Color) -> i16 {
+        color as i16
+    }
+}
+
+pub trait Backend {
+    /// Size of the whole screen as `(width, height)`.
+    fn screen_size(&self) -> (i32, i32);
+    fn create_surface(&self, x: i32, y: i32, width: i32, height: i32) -> Surface;
+    fn destroy_surface(&self, surface: Surface);
+    fn move_surface(&self, surface: Surface, x: i32, y: i32);
+    fn resize_surface(&self, surface: Surface, width: i32, height: i32);
+    fn clear(&self, surface: Surface);
+    /// Clears the row `y` of the surface starting from column `x`.
+    fn clear_to_eol(&self, surface: Surface, x: i32, y: i32);
+    fn print(&self, surface: Surface, x: i32, y: i32, text: &str);
+    /// Like `print`, with the colors and attributes of `style`.
+    fn print_styled(&self, surface: Surface, x: i32, y: i32, text: &str, style: Style);
+    /// Fills the surface with `color` and writes `text` starting at column `margin`.
+    fn draw_header(&self, surface: Surface, margin: i32, text: &str, color: Color);
+    /// Draws the vertical separator line to the left of a window.
+    fn draw_border(&self, surface: Surface);
+    /// Sets the stacking order of a surface. Surfaces start at 0, and the ones
+    /// with a higher z are kept on top of a surface when it is refreshed.
+    fn set_z_order(&self, surface: Surface, z: i32);
+    /// Pushes the contents of the surface to the screen.
+    fn refresh(&self, surface: Surface);
+    /// Reads a key without blocking. Returns -1 if no input is available.
+    fn get_key(&self, surface: Surface) -> i32;
+    /// Reads the mouse event that made `get_key` return `KEY_MOUSE`.
+    fn get_mouse(&self) -> Option<MouseEvent>;
+    /// Starts or stops reporting mouse events.
+    fn enable_mouse(&self, enabled: bool);
+    /// Changes the colors used for every `Color`. Surfaces already on the
+    /// screen change color when they are drawn again.
+    fn set_theme(&self, theme: &Theme);
+    /// The theme last set with `set_theme`, or the default one.
+    fn theme(&self) -> Theme;
+}
@@ -0,0 +1,354 @@
+//! Typed input events and key bindings.
+//!
+//! `Tcwm::next_event` reads input from the backend and turns it into an
+//! `Event`. Keys found in the manager's `KeyBindings` run their action or
+//! callback instead of being returned, and the changes they cause are reported
+//! as `FocusChanged` and `WindowClosed` events.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+
+use ncurses;
+
+use {Dimension, Direction, Handled, Id, MouseEvent, ResizeAmount, Tcwm, WindowSplitDirection,
+     RESIZE};
+
+/// A key press, decoded from the raw curses key codes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    /// A letter pressed together with Control.
+    Ctrl(char),
+    /// A character pressed together with Alt, sent as Escape followed by the
+    /// character.
+    Alt(char),
+    Enter,
+    Tab,
+    BackTab,
+    Backspace,
+    Esc,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Delete,
+    F(u8),
+    /// Any other curses key code.
+    Other(i32),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A key that is not bound to anything.
+    Key(Key),
+    /// The screen was resized to `(width, height)`. The layout has already
+    /// been updated.
+    Resize(i32, i32),
+    Mouse(MouseEvent),
+    /// A piece of input finished by the input handler of a window.
+    Input(Id, String),
+    /// The window with the given id got the focus.
+    FocusChanged(Id),
+    WindowClosed(Id),
+}
+
+/// Built-in actions that keys can be bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Split,
+    Delete,
+    ChangeFocus(Direction),
+    MoveFocused(Direction),
+    SetSplitDirection(WindowSplitDirection),
+    SetLayout(WindowSplitDirection),
+    Grow(Dimension, ResizeAmount),
+    Shrink(Dimension, ResizeAmount),
+    ToggleFloating,
+    ToggleFocusMode,
+    ToggleFullscreen,
+    ScrollUp(usize),
+    ScrollDown(usize),
+    PageUp,
+    PageDown,
+    ScrollToBottom,
+    SwitchWorkspace(String),
+    MoveToWorkspace(String),
+    /// Makes `Tcwm::run` return.
+    Quit,
+}
+
+type Callback = Rc<RefCell<dyn FnMut(&mut Tcwm)>>;
+
+#[derive(Clone)]
+enum Binding {
+    Action(Action),
+    Callback(Callback),
+}
+
+/// Maps keys to actions and user callbacks.
+#[derive(Clone, Default)]
+pub struct KeyBindings {
+    bindings: HashMap<Key, Binding>,
+}
+impl KeyBindings {
+    pub fn new() -> KeyBindings {
+        KeyBindings::default()
+    }
+    /// i3 style bindings using Alt as the modifier.
+    pub fn defaults() -> KeyBindings {
+        let mut b = KeyBindings::new();
+        b.bind(Key::Alt('\n'), Action::Split);
+        b.bind(Key::Alt('q'), Action::Delete);
+        b.bind(Key::Alt('h'), Action::ChangeFocus(Direction::Left));
+        b.bind(Key::Alt('j'), Action::ChangeFocus(Direction::Down));
+        b.bind(Key::Alt('k'), Action::ChangeFocus(Direction::Up));
+        b.bind(Key::Alt('l'), Action::ChangeFocus(Direction::Right));
+        b.bind(Key::Alt('H'), Action::MoveFocused(Direction::Left));
+        b.bind(Key::Alt('J'), Action::MoveFocused(Direction::Down));
+        b.bind(Key::Alt('K'), Action::MoveFocused(Direction::Up));
+        b.bind(Key::Alt('L'), Action::MoveFocused(Direction::Right));
+        b.bind(Key::Alt('v'), Action::SetSplitDirection(WindowSplitDirection::Horizontal));
+        b.bind(Key::Alt('b'), Action::SetSplitDirection(WindowSplitDirection::Vertical));
+        b.bind(Key::Alt('w'), Action::SetLayout(WindowSplitDirection::Tabbed));
+        b.bind(Key::Alt('s'), Action::SetLayout(WindowSplitDirection::Stacked));
+        b.bind(Key::Alt('e'), Action::SetLayout(WindowSplitDirection::Vertical));
+        b.bind(Key::Alt(' '), Action::ToggleFocusMode);
+        b.bind(Key::Alt('f'), Action::ToggleFullscreen);
+        b.bind(Key::Alt('F'), Action::ToggleFloating);
+        b.bind(Key::PageUp, Action::PageUp);
+        b.bind(Key::PageDown, Action::PageDown);
+        for n in 1..10 {
+            let name = n.to_string();
+            let c = name.chars().next().unwrap();
+            b.bind(Key::Alt(c), Action::SwitchWorkspace(name));
+        }
+        b
+    }
+    pub fn bind(&mut self, key: Key, action: Action) {
+        self.bindings.insert(key, Binding::Action(action));
+    }
+    /// Runs `f` when `key` is pressed.
+    pub fn bind_fn<F>(&mut self, key: Key, f: F)
+        where F: FnMut(&mut Tcwm) + 'static
+    {
+        self.bindings.insert(key, Binding::Callback(Rc::new(RefCell::new(f))));
+    }
+    pub fn unbind(&mut self, key: Key) {
+        self.bindings.remove(&key);
+    }
+    pub fn is_bound(&self, key: Key) -> bool {
+        self.bindings.contains_key(&key)
+    }
+}
+
+impl Key {
+    /// Decodes a curses key code. `next` reads the following key for
+    /// sequences such as Alt and multi-byte characters, and `unread` gives
+    /// back a key that turned out not to be part of the sequence.
+    fn decode<F, G>(code: i32, mut next: F, unread: G) -> Key
+        where F: FnMut() -> i32,
+              G: FnOnce(i32)
+    {
+        match code {
+            9 => Key::Tab,
+            10 | 13 | ncurses::KEY_ENTER => Key::Enter,
+            8 | 127 | ncurses::KEY_BACKSPACE => Key::Backspace,
+            27 => {
+                match next() {
+                    -1 => Key::Esc,
+                    // Alt goes with characters and Enter
+                    code @ (10 | 13 | 32..=126 | 0xc0..=0xf7) => {
+                        match Key::decode(code, next, unread) {
+                            Key::Char(c) => Key::Alt(c),
+                            Key::Enter => Key::Alt('\n'),
+                            _ => Key::Esc,
+                        }
+                    }
+                    code => {
+                        unread(code);
+                        Key::Esc
+                    }
+                }
+            }
+            1..=26 => Key::Ctrl((b'a' + code as u8 - 1) as char),
+            32..=126 => Key::Char(code as u8 as char),
+            0xc0..=0xf7 => {
+                // First byte of a UTF-8 sequence
+                let len = if code >= 0xf0 {
+                    4
+                } else if code >= 0xe0 {
+                    3
+                } else {
+                    2
+                };
+                let mut bytes = vec![code as u8];
+                for _ in 1..len {
+                    match next() {
+                        b @ 0x80..=0xbf => bytes.push(b as u8),
+                        _ => return Key::Other(code),
+                    }
+                }
+                match String::from_utf8(bytes).ok().and_then(|s| s.chars().next()) {
+                    Some(c) => Key::Char(c),
+                    None => Key::Other(code),
+                }
+            }
+            ncurses::KEY_UP => Key::Up,
+            ncurses::KEY_DOWN => Key::Down,
+            ncurses::KEY_LEFT => Key::Left,
+            ncurses::KEY_RIGHT => Key::Right,
+            ncurses::KEY_HOME => Key::Home,
+            ncurses::KEY_END => Key::End,
+            ncurses::KEY_PPAGE => Key::PageUp,
+            ncurses::KEY_NPAGE => Key::PageDown,
+            ncurses::KEY_DC => Key::Delete,
+            ncurses::KEY_BTAB => Key::BackTab,
+            code if code > ncurses::KEY_F0 && code <= ncurses::KEY_F0 + 63 => {
+                Key::F((code - ncurses::KEY_F0) as u8)
+            }
+            code => Key::Other(code),
+        }
+    }
+}
+
+impl Tcwm {
+    pub fn bindings(&mut self) -> &mut KeyBindings {
+        &mut self.bindings
+    }
+    pub fn set_bindings(&mut self, bindings: KeyBindings) {
+        self.bindings = bindings;
+    }
+    /// Returns the next event without blocking, or `None` if there is no
+    /// input.
+    pub fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(event);
+            }
+            self.update_terminals();
+            self.update_commands();
+            self.apply_messages();
+            match self.read_key() {
+                -1 => return self.events.pop_front(),
+                RESIZE => {
+                    self.resize();
+                    let (width, height) = self.backend.screen_size();
+                    return Some(Event::Resize(width, height));
+                }
+                ncurses::KEY_MOUSE => {
+                    // Queued so that focus changes caused by the click come first
+                    let event = self.backend.get_mouse().and_then(|m| self.handle_mouse(m));
+                    if let Some(event) = event {
+                        self.events.push_back(event);
+                    }
+                }
+                code => {
+                    let key = Key::decode(code,
+                                          || self.read_key(),
+                                          |code| self.unread.set(Some(code)));
+                    match self.bindings.bindings.get(&key).cloned() {
+                        Some(binding) => {
+                            self.dispatch(binding);
+                            if self.quit {
+                                return self.events.pop_front();
+                            }
+                        }
+                        None => {
+                            if let Some(event) = self.route_key(key) {
+                                return Some(event);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Handles events until `quit` is called or a key bound to
+    /// `Action::Quit` is pressed.
+    pub fn run<F>(&mut self, mut handler: F)
+        where F: FnMut(&mut Tcwm, Event)
+    {
+        self.quit = false;
+        while !self.quit {
+            match self.next_event() {
+                Some(event) => handler(self, event),
+                None => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+    }
+    pub fn quit(&mut self) {
+        self.quit = true;
+    }
+    /// Runs a built-in action on the focused window.
+    pub fn perform(&mut self, action: Action) {
+        match action {
+            Action::Split => {
+                self.split();
+            }
+            Action::Delete => self.delete(),
+            Action::ChangeFocus(direction) => self.change_focus(direction),
+            Action::MoveFocused(direction) => self.move_focused(direction),
+            Action::SetSplitDirection(direction) => self.set_split_direction(direction),
+            Action::SetLayout(layout) => self.set_layout(layout),
+            Action::Grow(dimension, amount) => self.grow(dimension, amount),
+            Action::Shrink(dimension, amount) => self.shrink(dimension, amount),
+            Action::ToggleFloating => self.toggle_floating(),
+            Action::ToggleFocusMode => self.toggle_focus_mode(),
+            Action::ToggleFullscreen => self.toggle_fullscreen(),
+            Action::ScrollUp(rows) => self.scroll_up(rows),
+            Action::ScrollDown(rows) => self.scroll_down(rows),
+            Action::PageUp => self.page_up(),
+            Action::PageDown => self.page_down(),
+            Action::ScrollToBottom => self.scroll_to_bottom(),
+            Action::SwitchWorkspace(ref name) => self.switch_workspace(name),
+            Action::MoveToWorkspace(ref name) => self.move_to_workspace(name),
+            Action::Quit => self.quit(),
+        }
+    }
+    /// Gives a key to the terminal pane or the input handler of the focused
+    /// window.
+    fn route_key(&mut self, key: Key) -> Option<Event> {
+        let w = self.focused();
+        let mut w = w.borrow_mut();
+        if let Some(ref mut t) = w.terminal {
+            if t.is_running() {
+                t.send_key(key);
+                return None;
+            }
+        }
+        let mut handler = match w.input_handler.take() {
+            Some(handler) => handler,
+            None => return Some(Event::Key(key)),
+        };
+        let handled = handler.handle_key(&mut w, key);
+        if w.input_handler.is_none() {
+            w.input_handler = Some(handler);
+        }
+        match handled {
+            Handled::Ignored => Some(Event::Key(key)),
+            Handled::Consumed => None,
+            Handled::Input(input) => Some(Event::Input(w.id(), input)),
+        }
+    }
+    fn read_key(&self) -> i32 {
+        if let Some(code) = self.unread.take() {
+            return code;
+        }
+        let w = self.focused();
+        let w = w.borrow();
+        self.backend.get_key(w.win)
+    }
+    fn dispatch(&mut self, binding: Binding) {
+        self.track_changes(|wm| match binding {
+            Binding::Action(action) => wm.perform(action),
+            Binding::Callback(f) => (*f.borrow_mut())(wm),
+        })
+    }
+}
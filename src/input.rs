@@ -0,0 +1,130 @@
+//! Per-window input handling.
+//!
+//! Keys that are not bound in the manager's `KeyBindings` go to the input
+//! handler of the focused window. Windows without a handler pass keys on to
+//! the application as `Event::Key`.
+
+use {Key, Window};
+
+/// What an `InputHandler` did with a key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Handled {
+    /// The key was not used. It is passed on as `Event::Key`.
+    Ignored,
+    Consumed,
+    /// The key finished a piece of input, which is passed on as
+    /// `Event::Input`.
+    Input(String),
+}
+
+pub trait InputHandler {
+    /// Called when the handler is set on `window`.
+    fn attach(&mut self, _window: &mut Window) {}
+    fn handle_key(&mut self, window: &mut Window, key: Key) -> Handled;
+}
+
+impl<F> InputHandler for F
+    where F: FnMut(&mut Window, Key) -> Handled
+{
+    fn handle_key(&mut self, window: &mut Window, key: Key) -> Handled {
+        self(window, key)
+    }
+}
+
+/// Edits a line of text shown at the bottom of the window. Enter finishes the
+/// line and leaves it in the window.
+pub struct LineEditor {
+    prompt: String,
+    line: String,
+}
+impl LineEditor {
+    pub fn new(prompt: &str) -> LineEditor {
+        LineEditor {
+            prompt: prompt.to_owned(),
+            line: String::new(),
+        }
+    }
+    fn show(&self, window: &mut Window) {
+        window.print_overwriting(format!("{}{}", self.prompt, self.line));
+    }
+}
+impl InputHandler for LineEditor {
+    fn attach(&mut self, window: &mut Window) {
+        self.show(window);
+    }
+    fn handle_key(&mut self, window: &mut Window, key: Key) -> Handled {
+        match key {
+            Key::Char(c) => self.line.push(c),
+            Key::Backspace => {
+                self.line.pop();
+            }
+            Key::Enter => {
+                let line = self.line.split_off(0);
+                // The finished line stays, the next one starts below it
+                window.overwritable = false;
+                self.show(window);
+                return Handled::Input(line);
+            }
+            _ => return Handled::Ignored,
+        }
+        self.show(window);
+        Handled::Consumed
+    }
+}
+
+/// Shows a list of items in the window. Up and Down move the selection and
+/// Enter picks the selected item.
+pub struct ListSelector {
+    items: Vec<String>,
+    selected: usize,
+}
+impl ListSelector {
+    pub fn new(items: Vec<String>) -> ListSelector {
+        ListSelector { items, selected: 0 }
+    }
+    pub fn selected(&self) -> Option<&str> {
+        self.items.get(self.selected).map(|s| &s[..])
+    }
+    fn show(&self, window: &mut Window) {
+        window.lines = self.items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let marker = if i == self.selected { "> " } else { "  " };
+                format!("{}{}", marker, item).into()
+            })
+            .collect();
+        window.overwritable = false;
+        // Scroll just enough to keep the selected item visible
+        let from_bottom = self.items.len().saturating_sub(self.selected + 1);
+        let height = window.content_height().max(1) as usize;
+        if from_bottom < window.scroll {
+            window.scroll = from_bottom;
+        } else if from_bottom >= window.scroll + height {
+            window.scroll = from_bottom + 1 - height;
+        }
+        window.reprint_buffer();
+    }
+}
+impl InputHandler for ListSelector {
+    fn attach(&mut self, window: &mut Window) {
+        self.show(window);
+    }
+    fn handle_key(&mut self, window: &mut Window, key: Key) -> Handled {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => {
+                self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1))
+            }
+            Key::Enter => {
+                return match self.selected() {
+                    Some(item) => Handled::Input(item.to_owned()),
+                    None => Handled::Consumed,
+                };
+            }
+            _ => return Handled::Ignored,
+        }
+        self.show(window);
+        Handled::Consumed
+    }
+}
@@ -0,0 +1,217 @@
+//! Saving and restoring layouts.
+//!
+//! A `Layout` describes the window tree of a manager: split directions,
+//! nesting, weights, headers, the focused child of every container and the
+//! floating windows. Window buffers can optionally be included. Layouts can be
+//! converted to and from JSON, so they can be stored between runs or shipped
+//! as presets.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde_json;
+
+use {BackendRef, CursesError, Window, WindowContainer, WindowPayload, WindowRef,
+     WindowSplitDirection};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Layout {
+    pub root: ContainerLayout,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub floating: Vec<FloatingLayout>,
+    #[serde(default)]
+    pub floating_focused: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Node {
+    Window(WindowLayout),
+    Container(ContainerLayout),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContainerLayout {
+    pub direction: WindowSplitDirection,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Index of the focused child.
+    #[serde(default)]
+    pub focus: usize,
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Header set by the user. Windows without one get a generated header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+    /// Buffer contents, without styles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FloatingLayout {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub window: WindowLayout,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl Layout {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+    pub fn from_json(s: &str) -> Result<Layout, CursesError> {
+        serde_json::from_str(s).map_err(|e| CursesError::InvalidLayout(e.to_string()))
+    }
+    fn validate(&self) -> Result<(), CursesError> {
+        fn container(c: &ContainerLayout) -> Result<(), CursesError> {
+            if c.children.is_empty() {
+                return Err(CursesError::InvalidLayout("container without children".into()));
+            }
+            if c.focus >= c.children.len() {
+                return Err(CursesError::InvalidLayout(format!("focus {} out of range",
+                                                              c.focus)));
+            }
+            weight(c.weight)?;
+            for child in c.children.iter() {
+                match *child {
+                    Node::Window(ref w) => weight(w.weight)?,
+                    Node::Container(ref c) => container(c)?,
+                }
+            }
+            Ok(())
+        }
+        fn weight(weight: f64) -> Result<(), CursesError> {
+            if weight > 0.0 && weight.is_finite() {
+                Ok(())
+            } else {
+                Err(CursesError::InvalidLayout(format!("invalid weight {}", weight)))
+            }
+        }
+        container(&self.root)
+    }
+}
+
+impl WindowLayout {
+    fn save(w: &Window, buffers: bool) -> WindowLayout {
+        WindowLayout {
+            weight: w.weight,
+            header: if w.header_set {
+                Some(w.header.clone())
+            } else {
+                None
+            },
+            lines: if buffers {
+                w.lines.iter().map(|line| line.text()).collect()
+            } else {
+                vec![]
+            },
+        }
+    }
+    fn restore(&self, w: &mut Window) {
+        w.weight = self.weight;
+        if let Some(ref header) = self.header {
+            w.header = header.clone();
+            w.header_set = true;
+        }
+        for line in self.lines.iter() {
+            w.push_line(line.into());
+        }
+    }
+}
+
+impl ContainerLayout {
+    fn save(c: &WindowContainer, buffers: bool) -> ContainerLayout {
+        ContainerLayout {
+            direction: c.direction,
+            weight: c.weight,
+            focus: c.focus,
+            children: c.payload
+                .iter()
+                .map(|pl| match *pl {
+                    WindowPayload::Window(ref w) => {
+                        Node::Window(WindowLayout::save(&w.borrow(), buffers))
+                    }
+                    WindowPayload::Container(ref c) => {
+                        Node::Container(ContainerLayout::save(&c.borrow(), buffers))
+                    }
+                })
+                .collect(),
+        }
+    }
+    fn payload(&self, backend: &BackendRef) -> Vec<WindowPayload> {
+        self.children
+            .iter()
+            .map(|child| match *child {
+                Node::Window(ref w) => {
+                    let mut win = Window::new(backend.clone());
+                    w.restore(&mut win);
+                    WindowPayload::Window(Rc::new(RefCell::new(win)))
+                }
+                Node::Container(ref c) => {
+                    let mut new = WindowContainer::with_payload(backend.clone(),
+                                                                c.payload(backend));
+                    new.direction = c.direction;
+                    new.weight = c.weight;
+                    new.focus = c.focus;
+                    WindowPayload::Container(Rc::new(RefCell::new(new)))
+                }
+            })
+            .collect()
+    }
+}
+
+impl WindowContainer {
+    /// Describes the current layout. Window buffers are included if `buffers`
+    /// is true.
+    pub fn save_layout(&self, buffers: bool) -> Layout {
+        Layout {
+            root: ContainerLayout::save(self, buffers),
+            floating: self.floating
+                .iter()
+                .map(|w| {
+                    let w = w.borrow();
+                    FloatingLayout {
+                        x: w.x,
+                        y: w.y,
+                        width: w.xmax,
+                        height: w.ymax,
+                        window: WindowLayout::save(&w, buffers),
+                    }
+                })
+                .collect(),
+            floating_focused: self.floating_focused,
+        }
+    }
+    /// Replaces all windows with the ones described by `layout`. Nothing is
+    /// changed if the layout is not valid.
+    pub fn restore_layout(&mut self, layout: &Layout) -> Result<(), CursesError> {
+        layout.validate()?;
+        self.payload = layout.root.payload(&self.backend);
+        self.direction = layout.root.direction;
+        self.focus = layout.root.focus;
+        self.floating = layout.floating
+            .iter()
+            .map(|f| {
+                let mut win = Window::new_window(self.backend.clone(),
+                                                 f.x,
+                                                 f.y,
+                                                 (f.width, f.height));
+                f.window.restore(&mut win);
+                Rc::new(RefCell::new(win)) as WindowRef
+            })
+            .collect();
+        self.floating_focused = layout.floating_focused && !self.floating.is_empty();
+        self.resize();
+        Ok(())
+    }
+}
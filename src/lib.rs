@@ -1,89 +1,312 @@
+extern crate libc;
 extern crate ncurses;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate unicode_segmentation;
+extern crate unicode_width;
 extern crate uuid;
-
-use ncurses::*;
+extern crate vt100;
 
 use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::error;
 use std::fmt;
 use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
-use std::sync::{Once, ONCE_INIT};
+use std::sync::mpsc;
 
-pub type Id = uuid::Uuid;
+mod ansi;
+mod arrange;
+pub mod backend;
+mod event;
+mod input;
+mod layout;
+mod mouse;
+mod process;
+mod sender;
+mod terminal;
+#[cfg(test)]
+mod tests;
+mod text;
+mod theme;
 
-static INIT: Once = ONCE_INIT;
-static mut ROOT_CONTAINER: Option<*mut WindowContainer> = None;
+pub use ansi::AnsiParser;
+pub use backend::{Backend, BackendRef, Color, CursesBackend, HeadlessBackend, MouseEvent,
+                  MouseKind, Surface};
+pub use event::{Action, Event, Key, KeyBindings};
+pub use input::{Handled, InputHandler, LineEditor, ListSelector};
+pub use layout::{ContainerLayout, FloatingLayout, Layout, Node, WindowLayout};
+pub use sender::WindowSender;
+pub use text::{display_width, Span, Style, StyledText};
+pub use theme::{ColorPair, Theme, ThemeColor};
+
+pub type Id = uuid::Uuid;
 
 #[allow(dead_code)]
 fn log<T: Into<String>>(s: T) {
     use ::std::io::Write;
-    let mut file = ::std::fs::OpenOptions::new()
-        .write(true)
+    let file = ::std::fs::OpenOptions::new()
         .create(false)
         .append(true)
-        .open("out.log")
-        .unwrap();
-    let _ = file.write(s.into().as_bytes());
-    let _ = file.write(b"\n");
+        .open("out.log");
+    if let Ok(mut file) = file {
+        let _ = file.write(s.into().as_bytes());
+        let _ = file.write(b"\n");
+    }
 }
 
-pub struct Tcwm;
+/// The window manager. It dereferences to the root container of the visible
+/// workspace.
+pub struct Tcwm {
+    backend: BackendRef,
+    workspaces: Vec<Workspace>,
+    current: usize,
+    bindings: KeyBindings,
+    // Events waiting to be returned from next_event
+    events: VecDeque<Event>,
+    // A key read while decoding the previous one that belongs to the next
+    unread: Cell<Option<i32>>,
+    quit: bool,
+    // Window whose left border or header is being dragged
+    drag: Option<(Id, Dimension)>,
+    // Both ends of the channel of WindowSenders
+    sender: mpsc::Sender<sender::Message>,
+    messages: mpsc::Receiver<sender::Message>,
+}
+struct Workspace {
+    name: String,
+    root: WindowContainer,
+}
 impl Tcwm {
     pub fn new() -> Result<Tcwm, CursesError> {
-        let mut ret = Err(CursesError::CursesAlreadyInitialized);
-        INIT.call_once(|| {
-            ::std::fs::File::create("out.log").unwrap();
-            initscr();
-            if !has_colors() {
-                panic!("No colors");
-            }
-            curs_set(ncurses::CURSOR_VISIBILITY::CURSOR_INVISIBLE);
-            noecho();
-            refresh();
-            start_color();
-
-            init_pair(Color::Selection.into(), COLOR_GREEN, COLOR_BLACK);
-            init_pair(Color::Status.into(), COLOR_WHITE, COLOR_BLUE);
-            init_pair(Color::StatusSelected.into(), COLOR_BLACK, COLOR_CYAN);
-            init_pair(Color::Default.into(), COLOR_GREEN, COLOR_BLACK);
-
-            let root = Box::new(WindowContainer::new());
-            unsafe {
-                ROOT_CONTAINER = Some(Box::into_raw(root));
+        let backend = CursesBackend::new()?;
+        ::std::fs::File::create("out.log").unwrap();
+        Ok(Tcwm::with_backend(Rc::new(backend)))
+    }
+    /// Like `new`, but draws with the colors of `theme`.
+    pub fn with_theme(theme: Theme) -> Result<Tcwm, CursesError> {
+        let mut wm = Tcwm::new()?;
+        wm.set_theme(theme);
+        Ok(wm)
+    }
+    /// Creates a manager that draws using `backend`. Managers are independent
+    /// of each other, so several of them can share one backend. The theme
+    /// belongs to the backend, so they share that too.
+    pub fn with_backend(backend: BackendRef) -> Tcwm {
+        let root = WindowContainer::new(backend.clone());
+        let (sender, messages) = mpsc::channel();
+        Tcwm {
+            backend,
+            workspaces: vec![Workspace {
+                                 name: "1".into(),
+                                 root,
+                             }],
+            current: 0,
+            bindings: KeyBindings::new(),
+            events: VecDeque::new(),
+            unread: Cell::new(None),
+            quit: false,
+            drag: None,
+            sender,
+            messages,
+        }
+    }
+    /// The colors of the backend.
+    pub fn theme(&self) -> Theme {
+        self.backend.theme()
+    }
+    /// Changes the colors of all windows and redraws the screen with them.
+    /// Other managers on the same backend draw with them from then on.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.backend.set_theme(&theme);
+        self.resize();
+    }
+    pub fn current_workspace(&self) -> &str {
+        &self.workspaces[self.current].name
+    }
+    /// Names of all workspaces along with their windows.
+    pub fn workspaces(&self) -> Vec<(&str, Vec<WindowRef>)> {
+        self.workspaces
+            .iter()
+            .map(|ws| (&ws.name[..], ws.root.all_windows()))
+            .collect()
+    }
+    /// Shows the workspace called `name`, creating it if it does not exist.
+    pub fn switch_workspace(&mut self, name: &str) {
+        let index = self.workspace_index(name);
+        if index == self.current {
+            return;
+        }
+        self.workspaces[self.current].root.hide();
+        self.current = index;
+        self.workspaces[self.current].root.resize();
+    }
+    /// Moves the focused window to the workspace called `name`, creating it if
+    /// it does not exist. The last window of a workspace cannot be moved.
+    pub fn move_to_workspace(&mut self, name: &str) {
+        let index = self.workspace_index(name);
+        if index == self.current {
+            return;
+        }
+        let (window, floating) = {
+            let root = &mut self.workspaces[self.current].root;
+            if root.floating_focused {
+                let window = root.floating.pop();
+                root.floating_focused = !root.floating.is_empty();
+                (window, true)
+            } else {
+                (root.take_focused(), false)
             }
-            ret = Ok(Tcwm)
-        });
-        ret
+        };
+        let window = match window {
+            Some(window) => window,
+            None => return,
+        };
+        {
+            let (width, height) = self.backend.screen_size();
+            let target = &mut self.workspaces[index].root;
+            if floating {
+                target.floating.push(window);
+            } else {
+                target.insert_window(window);
+            }
+            target.layout(0, 0, width, height);
+            target.hide();
+        }
+        self.workspaces[self.current].root.resize();
+    }
+    /// Finds a window on any workspace by its id.
+    pub fn window(&self, id: Id) -> Result<WindowRef, CursesError> {
+        let index = self.workspace_of(id)?;
+        Ok(self.workspaces[index].root.find_window(id).unwrap())
+    }
+    /// Runs `f` on the window `id`. Only that window is drawn to, and the
+    /// focus stays where it is.
+    pub fn with_window<F, T>(&mut self, id: Id, f: F) -> Result<T, CursesError>
+        where F: FnOnce(&mut Window) -> T
+    {
+        let w = self.window(id)?;
+        let mut w = w.borrow_mut();
+        Ok(f(&mut w))
+    }
+    pub fn set_input_handler<H>(&mut self, id: Id, handler: H) -> Result<(), CursesError>
+        where H: InputHandler + 'static
+    {
+        self.with_window(id, |w| w.set_input_handler(handler))
+    }
+    pub fn print_to<T>(&mut self, id: Id, text: T) -> Result<(), CursesError>
+        where T: Into<StyledText>
+    {
+        self.with_window(id, |w| w.print(text))
+    }
+    pub fn print_overwriting_to<T>(&mut self, id: Id, text: T) -> Result<(), CursesError>
+        where T: Into<StyledText>
+    {
+        self.with_window(id, |w| w.print_overwriting(text))
+    }
+    pub fn write_to(&mut self, id: Id, s: &str) -> Result<(), CursesError> {
+        self.with_window(id, |w| w.write(s))
+    }
+    pub fn set_header_of(&mut self, id: Id, header: &str) -> Result<(), CursesError> {
+        self.with_window(id, |w| w.set_header(header))
+    }
+    /// Focuses a window, switching to its workspace if needed.
+    pub fn focus_window(&mut self, id: Id) -> Result<(), CursesError> {
+        let index = self.workspace_of(id)?;
+        if index != self.current {
+            let name = self.workspaces[index].name.clone();
+            self.switch_workspace(&name);
+        }
+        self.workspaces[index].root.focus_window(id);
+        Ok(())
+    }
+    /// Closes a window on any workspace. The last tiled window of a
+    /// workspace cannot be closed.
+    pub fn close_window(&mut self, id: Id) -> Result<(), CursesError> {
+        let index = self.workspace_of(id)?;
+        let root = &mut self.workspaces[index].root;
+        if root.window_count() == 1 && root.find(id).is_some() {
+            return Err(CursesError::LastWindow(id));
+        }
+        root.remove_window(id);
+        if index == self.current {
+            root.resize();
+        } else {
+            let (width, height) = self.backend.screen_size();
+            root.layout(0, 0, width, height);
+        }
+        Ok(())
+    }
+    fn workspace_of(&self, id: Id) -> Result<usize, CursesError> {
+        self.workspaces
+            .iter()
+            .position(|ws| ws.root.find_window(id).is_some())
+            .ok_or(CursesError::WindowNotFound(id))
+    }
+    /// Runs `f` and queues events for the windows it closed and the focus
+    /// change it caused.
+    fn track_changes<F>(&mut self, f: F)
+        where F: FnOnce(&mut Tcwm)
+    {
+        let focused = self.focused().borrow().id();
+        let windows = self.window_ids();
+        f(self);
+        let remaining = self.window_ids();
+        for id in windows.into_iter().filter(|id| !remaining.contains(id)) {
+            self.events.push_back(Event::WindowClosed(id));
+        }
+        let now_focused = self.focused().borrow().id();
+        if now_focused != focused {
+            self.events.push_back(Event::FocusChanged(now_focused));
+        }
+    }
+    fn window_ids(&self) -> Vec<Id> {
+        self.workspaces
+            .iter()
+            .flat_map(|ws| ws.root.all_windows())
+            .map(|w| w.borrow().id())
+            .collect()
+    }
+    fn workspace_index(&mut self, name: &str) -> usize {
+        match self.workspaces.iter().position(|ws| ws.name == name) {
+            Some(index) => index,
+            None => {
+                let mut root = WindowContainer::new(self.backend.clone());
+                root.hide();
+                self.workspaces.push(Workspace {
+                    name: name.into(),
+                    root,
+                });
+                self.workspaces.len() - 1
+            }
+        }
     }
 }
 impl Deref for Tcwm {
     type Target = WindowContainer;
     fn deref(&self) -> &Self::Target {
-        unsafe { &*ROOT_CONTAINER.unwrap() }
+        &self.workspaces[self.current].root
     }
 }
 impl DerefMut for Tcwm {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { &mut *ROOT_CONTAINER.unwrap() }
-    }
-}
-impl Drop for Tcwm {
-    fn drop(&mut self) {
-        endwin();
-        unsafe {
-            let root = Box::from_raw(ROOT_CONTAINER.unwrap());
-            drop(root);
-        }
+        &mut self.workspaces[self.current].root
     }
 }
 
-
 pub type WindowRef = Rc<RefCell<Window>>;
 pub type ContainerRef = Rc<RefCell<WindowContainer>>;
 pub const RESIZE: i32 = ncurses::KEY_RESIZE;
 
+// Room for the header and at least one row or column of content
+const MIN_WINDOW_SIZE: i32 = 2;
+const DEFAULT_SCROLLBACK: usize = 1000;
+
 pub struct WindowContainer {
     id: Id,
+    backend: BackendRef,
     payload: Vec<WindowPayload>,
     direction: WindowSplitDirection,
     container_x: i32,
@@ -91,6 +314,14 @@ pub struct WindowContainer {
     width: i32,
     height: i32,
     focus: usize,
+    weight: f64,
+    // Tabs of a tabbed container or title rows of a stacked one
+    titles: Vec<Surface>,
+    // Floating windows of the root container, topmost last
+    floating: Vec<WindowRef>,
+    floating_focused: bool,
+    // Window shown over the whole screen and its place before that
+    fullscreen: Option<(WindowRef, (i32, i32, i32, i32))>,
     root: bool, // TODO: This is an ugly hack
 }
 impl PartialEq for WindowContainer {
@@ -102,22 +333,17 @@ impl Eq for WindowContainer {}
 impl fmt::Debug for WindowContainer {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fn format(this: &WindowContainer, fmt: &mut fmt::Formatter, indent: usize) -> fmt::Result {
-            let this_indent = ::std::iter::repeat(" ").take(indent).collect::<String>();
-            let others_indent = ::std::iter::repeat(" ").take(indent + 2).collect::<String>();
-            try!(write!(fmt,
-                        "{}{}{:?} ({})]\n",
-                        this_indent,
-                        "[C ",
-                        this.direction,
-                        this.id));
+            let this_indent = " ".repeat(indent);
+            let others_indent = " ".repeat(indent + 2);
+            writeln!(fmt, "{}[C {:?} ({})]", this_indent, this.direction, this.id)?;
             for pl in this.payload.iter() {
                 if pl.is_container() {
                     let pl = pl.as_container();
-                    try!(format(&*pl.borrow(), fmt, indent + 4));
+                    format(&pl.borrow(), fmt, indent + 4)?;
                 } else {
                     let pl = pl.as_window();
                     let pl = pl.borrow();
-                    try!(write!(fmt, "{}{} ({})]\n", others_indent, "[W", pl.id));
+                    writeln!(fmt, "{}[W ({})]", others_indent, pl.id)?;
                 };
             }
             Ok(())
@@ -126,7 +352,7 @@ impl fmt::Debug for WindowContainer {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -145,6 +371,7 @@ impl WindowPayload {
             WindowPayload::Container(_) => true,
         }
     }
+    #[allow(dead_code)]
     fn is_window(&self) -> bool {
         !self.is_container()
     }
@@ -160,124 +387,339 @@ impl WindowPayload {
             _ => panic!("Not a window"),
         }
     }
+    fn weight(&self) -> f64 {
+        match *self {
+            WindowPayload::Window(ref w) => w.borrow().weight,
+            WindowPayload::Container(ref c) => c.borrow().weight,
+        }
+    }
+    fn set_weight(&self, weight: f64) {
+        match *self {
+            WindowPayload::Window(ref w) => w.borrow_mut().weight = weight,
+            WindowPayload::Container(ref c) => c.borrow_mut().weight = weight,
+        }
+    }
+    fn layout(&self, x: i32, y: i32, width: i32, height: i32) {
+        match *self {
+            WindowPayload::Window(ref w) => {
+                let mut w = w.borrow_mut();
+                w.x = x;
+                w.y = y;
+                w.xmax = width;
+                w.ymax = height;
+                WindowContainer::reresize_window(&mut w);
+            }
+            WindowPayload::Container(ref c) => c.borrow_mut().layout(x, y, width, height),
+        }
+    }
+    /// Title shown for the payload in tab bars and stacked title rows.
+    fn title(&self) -> String {
+        match *self {
+            WindowPayload::Window(ref w) => w.borrow().header.clone(),
+            WindowPayload::Container(ref c) => {
+                let c = c.borrow();
+                c.payload[c.focus].title()
+            }
+        }
+    }
+    /// Marks everything in the payload as not being on the screen.
+    fn hide(&self) {
+        match *self {
+            WindowPayload::Window(ref w) => w.borrow_mut().visible = false,
+            WindowPayload::Container(ref c) => {
+                for pl in c.borrow().payload.iter() {
+                    pl.hide();
+                }
+            }
+        }
+    }
 }
 impl WindowContainer {
-    fn new() -> WindowContainer {
-        WindowContainer::new_container(0, 0, None)
+    fn new(backend: BackendRef) -> WindowContainer {
+        WindowContainer::new_container(backend, 0, 0, None)
     }
-    fn new_container(x: i32, y: i32, window: Option<WindowRef>) -> WindowContainer {
+    fn new_container(backend: BackendRef,
+                     x: i32,
+                     y: i32,
+                     window: Option<WindowRef>)
+                     -> WindowContainer {
         let is_root = window.is_none();
-        let win = window.unwrap_or(Rc::new(RefCell::new(Window::new())));
+        let win = window.unwrap_or_else(|| Rc::new(RefCell::new(Window::new(backend.clone()))));
         let (width, height) = {
             let win = win.borrow();
             (win.xmax, win.ymax)
         };
-        let mut root = WindowContainer {
+        let mut root = WindowContainer::with_payload(backend, vec![WindowPayload::Window(win)]);
+        root.container_x = x;
+        root.container_y = y;
+        root.width = width;
+        root.height = height;
+        root.root = is_root;
+        root.refresh_windows(false);
+        root
+    }
+    fn with_payload(backend: BackendRef, payload: Vec<WindowPayload>) -> WindowContainer {
+        WindowContainer {
             id: uuid::Uuid::new_v4(),
-            payload: vec![WindowPayload::Window(win)],
+            backend,
+            payload,
             direction: WindowSplitDirection::Vertical,
-            container_x: x,
-            container_y: y,
-            width: width,
-            height: height,
+            container_x: 0,
+            container_y: 0,
+            width: 0,
+            height: 0,
             focus: 0,
-            root: is_root,
-        };
-        root.refresh_windows(false);
-        root
+            weight: 1.0,
+            titles: vec![],
+            floating: vec![],
+            floating_focused: false,
+            fullscreen: None,
+            root: false,
+        }
     }
     pub fn change_focus(&mut self, direction: Direction) {
-        let _ = self.change_focus_internal(direction);
+        if self.fullscreen.is_some() {
+            return;
+        }
+        if self.floating_focused {
+            // Cycle through the floating windows
+            if self.floating.len() > 1 {
+                match direction {
+                    Direction::Right | Direction::Down => {
+                        let w = self.floating.remove(0);
+                        self.floating.push(w);
+                    }
+                    Direction::Left | Direction::Up => {
+                        let w = self.floating.pop().unwrap();
+                        self.floating.insert(0, w);
+                    }
+                }
+                self.resize();
+            }
+            return;
+        }
+        self.change_focus_tiled(direction);
         self.refresh_windows(false);
     }
-    fn change_focus_internal(&mut self, direction: Direction) -> Result<(), ()> {
-        if self.payload[self.focus].is_container() {
-            let c = self.payload[self.focus].as_container();
-            return match c.borrow_mut().change_focus_internal(direction) {
-                Err(_) => self.do_focus_change(direction),
-                Ok(_) => Ok(()),
+    /// Moves the focus to the nearest window on the screen in `direction`.
+    /// Inside tabbed and stacked containers the windows of the shown child
+    /// come first, then the other tabs, and only then windows outside the
+    /// container.
+    fn change_focus_tiled(&mut self, direction: Direction) {
+        let from = {
+            let w = self.with_focused_container(|f| f.focused_window());
+            let w = w.borrow();
+            (w.x, w.y, w.xmax, w.ymax)
+        };
+        let mut chain = self.focus_chain();
+        chain.reverse();
+        for c in chain {
+            let (area, one_visible) = {
+                let c = c.borrow();
+                ((c.container_x, c.container_y, c.width, c.height),
+                 c.direction.shows_one_child())
             };
-        } else {
-            self.do_focus_change(direction)
+            if !one_visible {
+                continue;
+            }
+            if let Some(id) = self.nearest_window(from, direction, area) {
+                self.focus_path(id);
+                return;
+            }
+            if c.borrow_mut().do_focus_change(direction).is_ok() {
+                return;
+            }
+        }
+        let screen = (self.container_x, self.container_y, self.width, self.height);
+        if let Some(id) = self.nearest_window(from, direction, screen) {
+            self.focus_path(id);
+        } else if self.direction.shows_one_child() {
+            let _ = self.do_focus_change(direction);
         }
     }
+    /// The closest visible tiled window inside `area` that lies past the edge
+    /// of `from` in `direction`. Windows that overlap `from` across the
+    /// direction of movement are preferred.
+    fn nearest_window(&self,
+                      from: (i32, i32, i32, i32),
+                      direction: Direction,
+                      area: (i32, i32, i32, i32))
+                      -> Option<Id> {
+        let (fx, fy, fw, fh) = from;
+        let (ax, ay, aw, ah) = area;
+        self.all_windows()
+            .iter()
+            .filter(|w| !self.is_floating(w))
+            .filter_map(|w| {
+                let w = w.borrow();
+                let inside = w.x >= ax && w.y >= ay && w.x + w.xmax <= ax + aw &&
+                             w.y + w.ymax <= ay + ah;
+                if !w.visible || !inside {
+                    return None;
+                }
+                // Distance along the movement and overlap across it
+                let (distance, overlap, offset) = match direction {
+                    Direction::Left => {
+                        (fx - (w.x + w.xmax),
+                         overlap(fy, fh, w.y, w.ymax),
+                         (2 * w.y + w.ymax - 2 * fy - fh).abs())
+                    }
+                    Direction::Right => {
+                        (w.x - (fx + fw),
+                         overlap(fy, fh, w.y, w.ymax),
+                         (2 * w.y + w.ymax - 2 * fy - fh).abs())
+                    }
+                    Direction::Up => {
+                        (fy - (w.y + w.ymax),
+                         overlap(fx, fw, w.x, w.xmax),
+                         (2 * w.x + w.xmax - 2 * fx - fw).abs())
+                    }
+                    Direction::Down => {
+                        (w.y - (fy + fh),
+                         overlap(fx, fw, w.x, w.xmax),
+                         (2 * w.x + w.xmax - 2 * fx - fw).abs())
+                    }
+                };
+                if distance < 0 {
+                    return None;
+                }
+                Some(((overlap <= 0, distance, offset), w.id))
+            })
+            .min_by_key(|&(key, _)| key)
+            .map(|(_, id)| id)
+    }
+    /// Containers from the root towards the focused tiled window, not
+    /// including the root.
+    fn focus_chain(&self) -> Vec<ContainerRef> {
+        let mut chain = vec![];
+        let mut next = match self.payload[self.focus] {
+            WindowPayload::Container(ref c) => Some(c.clone()),
+            WindowPayload::Window(_) => None,
+        };
+        while let Some(c) = next {
+            next = {
+                let c = c.borrow();
+                match c.payload[c.focus] {
+                    WindowPayload::Container(ref c) => Some(c.clone()),
+                    WindowPayload::Window(_) => None,
+                }
+            };
+            chain.push(c);
+        }
+        chain
+    }
     pub fn delete(&mut self) {
         log(format!("{:?}", self));
-        if self.id == unsafe { (*ROOT_CONTAINER.unwrap()).id } && self.payload.len() == 1 {
-            // Only window, cannot delete
+        if self.floating_focused {
+            self.floating.pop();
+            self.floating_focused = !self.floating.is_empty();
+            self.resize();
             return;
         }
-        let (window_deleted, delete_container) = self.with_focused_container_mut(|f| {
-            let fwid = {
-                let f = f.focused_window();
-                let f = f.borrow();
-                f.id
-            };
-            let fw_pos = f.payload.iter().position(|w| {
-                if w.is_window() {
-                    let w = w.as_window();
-                    let w = w.borrow();
-                    return w.id == fwid;
-                } else {
-                    false
-                }
-            });
-            match fw_pos {
-                Some(pos) => {
-                    log("deleting window");
-                    f.payload.remove(pos);
-                    if f.focus > 0 {
-                        f.focus -= 1;
-                    }
-                    let delete_container = {
-                        if f.payload.len() == 0 {
-                            log(format!("marking {} to be deleted", f.id));
-                            Some(f.id)
-                        } else {
-                            None
-                        }
-                    };
-                    (true, delete_container)
-                }
-                None => (false, None),
+        if self.take_focused().is_some() {
+            log("window deleted, resizing");
+            self.resize();
+        }
+    }
+    /// Removes the focused tiled window from the tree and returns it. The
+    /// last window is never removed.
+    fn take_focused(&mut self) -> Option<WindowRef> {
+        if self.window_count() == 1 {
+            // Only window, cannot delete
+            return None;
+        }
+        let window = self.with_focused_container_mut(|f| {
+            log("deleting window");
+            let pos = f.focus;
+            let window = f.payload.remove(pos).as_window();
+            if f.focus > 0 {
+                f.focus -= 1;
             }
+            window
         });
-        if delete_container.is_some() {
-            let cont_id = delete_container.unwrap();
-            if let Some(WindowPayload::Container(container)) = self.find(cont_id) {
-                log(format!("Searching for parent of {} starting from {}",
-                            cont_id,
-                            self.id));
-                self.with_parent_of(&*container.borrow(), |p| {
-                    log(format!("Parent {} found", p.id));
-                    log(format!("Parent's payload is {}", p.payload.len()));
-                    let pos = p.payload.iter().position(|w| {
-                        if w.is_container() {
-                            let c = w.as_container();
-                            let cid = c.borrow().id;
-                            log(format!("{} == {}", cid, cont_id));
-                            return cid == cont_id;
-                        }
-                        false
-                    });
-                    log(format!("{:?}", pos));
-                    if let Some(pos) = pos {
-                        log("deleting container");
-                        p.payload.remove(pos);
-                        if p.focus > 0 {
-                            p.focus -= 1;
+        self.prune();
+        Some(window)
+    }
+    /// Removes the containers that have no windows left in them and replaces
+    /// the ones holding nothing but another container with that container,
+    /// however deeply they are nested. A removed container that had the focus
+    /// passes it to the entry before it.
+    fn prune(&mut self) {
+        let mut i = 0;
+        while i < self.payload.len() {
+            let replacement = match self.payload[i] {
+                WindowPayload::Container(ref c) => {
+                    let mut c = c.borrow_mut();
+                    c.prune();
+                    match c.payload.len() {
+                        0 => Some(None),
+                        1 if c.payload[0].is_container() => {
+                            let only = c.payload.pop().unwrap();
+                            only.set_weight(c.weight);
+                            Some(Some(only))
                         }
+                        _ => None,
                     }
-                });
+                }
+                WindowPayload::Window(_) => None,
+            };
+            match replacement {
+                Some(None) => {
+                    log("deleting container");
+                    self.payload.remove(i);
+                    if self.focus >= i && self.focus > 0 {
+                        self.focus -= 1;
+                    }
+                }
+                Some(Some(only)) => {
+                    self.payload[i] = only;
+                    i += 1;
+                }
+                None => i += 1,
             }
         }
-        if window_deleted {
-            log("window deleted, resizing");
-            // self.on_resize(true, (0,0,0,0));
-            // self.refresh_windows(true);
-            WindowContainer::resize();
+        // A root holding a single container takes over its entries
+        if self.root && self.payload.len() == 1 && self.payload[0].is_container() {
+            let inner = self.payload.pop().unwrap().as_container();
+            let mut inner = inner.borrow_mut();
+            self.payload = inner.payload.split_off(0);
+            self.direction = inner.direction;
+            self.focus = inner.focus;
         }
     }
+    /// All windows in the tree followed by the floating windows.
+    fn all_windows(&self) -> Vec<WindowRef> {
+        let mut ret = vec![];
+        for pl in self.payload.iter() {
+            match *pl {
+                WindowPayload::Window(ref w) => ret.push(w.clone()),
+                WindowPayload::Container(ref c) => ret.extend(c.borrow().all_windows()),
+            }
+        }
+        ret.extend(self.floating.iter().cloned());
+        ret
+    }
+    /// Takes every window off the screen. They are drawn again on the next
+    /// refresh.
+    fn hide(&mut self) {
+        for pl in self.payload.iter() {
+            pl.hide();
+        }
+        for w in self.floating.iter() {
+            let mut w = w.borrow_mut();
+            w.visible = false;
+            w.set_z_order(0);
+        }
+    }
+    fn window_count(&self) -> usize {
+        self.payload
+            .iter()
+            .map(|pl| match *pl {
+                WindowPayload::Window(_) => 1,
+                WindowPayload::Container(ref c) => c.borrow().window_count(),
+            })
+            .sum()
+    }
     fn do_focus_change(&mut self, direction: Direction) -> Result<(), ()> {
         match direction {
             Direction::Left | Direction::Up => {
@@ -308,16 +750,17 @@ impl WindowContainer {
     }
     fn find(&self, id: Id) -> Option<WindowPayload> {
         for pl in self.payload.iter() {
-            match pl {
-                &WindowPayload::Container(ref cpl) => {
+            match *pl {
+                WindowPayload::Container(ref cpl) => {
                     let cpl = cpl.borrow();
                     if cpl.id == id {
                         return Some(pl.clone());
-                    } else {
-                        return cpl.find(id);
+                    }
+                    if let Some(found) = cpl.find(id) {
+                        return Some(found);
                     }
                 }
-                &WindowPayload::Window(ref win) => {
+                WindowPayload::Window(ref win) => {
                     let w = win.borrow();
                     if w.id == id {
                         return Some(pl.clone());
@@ -327,19 +770,99 @@ impl WindowContainer {
         }
         None
     }
-    pub fn print(&mut self, s: &str) {
-        self.with_focused_container_mut(|f| {
-            let f = f.focused_window();
-            let mut f = f.borrow_mut();
-            f.print(s);
-        })
+    /// Finds a tiled or floating window by its id.
+    fn find_window(&self, id: Id) -> Option<WindowRef> {
+        match self.find(id) {
+            Some(WindowPayload::Window(w)) => Some(w),
+            _ => self.floating.iter().find(|w| w.borrow().id == id).cloned(),
+        }
     }
-    pub fn print_overwriting(&mut self, s: &str) {
-        self.with_focused_container_mut(|f| {
-            let f = f.focused_window();
-            let mut f = f.borrow_mut();
-            f.print_overwriting(s);
-        })
+    /// Points the focus of every container on the way to the tiled window
+    /// `id` towards it.
+    fn focus_path(&mut self, id: Id) -> bool {
+        for (i, pl) in self.payload.iter().enumerate() {
+            let found = match *pl {
+                WindowPayload::Window(ref w) => w.borrow().id == id,
+                WindowPayload::Container(ref c) => c.borrow_mut().focus_path(id),
+            };
+            if found {
+                self.focus = i;
+                return true;
+            }
+        }
+        false
+    }
+    fn focus_window(&mut self, id: Id) {
+        if let Some(pos) = self.floating.iter().position(|w| w.borrow().id == id) {
+            let w = self.floating.remove(pos);
+            self.floating.push(w);
+            self.floating_focused = true;
+            self.resize();
+        } else if self.focus_path(id) {
+            self.floating_focused = false;
+            self.refresh_windows(false);
+        }
+    }
+    /// Removes the window `id` without touching the focus. The last tiled
+    /// window is never removed.
+    fn remove_window(&mut self, id: Id) {
+        if let Some(pos) = self.floating.iter().position(|w| w.borrow().id == id) {
+            self.floating.remove(pos);
+            self.floating_focused = self.floating_focused && !self.floating.is_empty();
+            return;
+        }
+        let previous = self.with_focused_container(|f| f.focused_window().borrow().id);
+        if self.focus_path(id) {
+            self.take_focused();
+            self.focus_path(previous);
+        }
+    }
+    pub fn print<T>(&mut self, text: T)
+        where T: Into<StyledText>
+    {
+        self.with_focused_window_mut(|w| w.print(text))
+    }
+    pub fn print_overwriting<T>(&mut self, text: T)
+        where T: Into<StyledText>
+    {
+        self.with_focused_window_mut(|w| w.print_overwriting(text))
+    }
+    pub fn write(&mut self, s: &str) {
+        self.with_focused_window_mut(|w| w.write(s))
+    }
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.with_focused_window_mut(|w| w.scroll_up(rows))
+    }
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.with_focused_window_mut(|w| w.scroll_down(rows))
+    }
+    pub fn scroll_to_bottom(&mut self) {
+        self.with_focused_window_mut(|w| w.scroll_to_bottom())
+    }
+    pub fn page_up(&mut self) {
+        self.with_focused_window_mut(|w| w.page_up())
+    }
+    pub fn page_down(&mut self) {
+        self.with_focused_window_mut(|w| w.page_down())
+    }
+    fn with_focused_window_mut<F, T>(&mut self, f: F) -> T
+        where F: FnOnce(&mut Window) -> T
+    {
+        let w = self.focused();
+        let mut w = w.borrow_mut();
+        f(&mut w)
+    }
+    /// The window that has the focus, floating or tiled.
+    fn focused(&self) -> WindowRef {
+        if let Some((ref w, _)) = self.fullscreen {
+            return w.clone();
+        }
+        if self.floating_focused {
+            if let Some(w) = self.floating.last() {
+                return w.clone();
+            }
+        }
+        self.with_focused_container(|f| f.focused_window())
     }
     pub fn set_split_direction(&mut self, direction: WindowSplitDirection) {
         if self.payload.len() == 1 {
@@ -353,41 +876,133 @@ impl WindowContainer {
         let win = self.payload.remove(self.focus);
         match win {
             WindowPayload::Window(win_payload) => {
-                let (x, y) = {
-                    let win = win_payload.borrow();
-                    (win.x, win.y)
+                let (x, y, weight) = {
+                    let mut win = win_payload.borrow_mut();
+                    // The new container takes the place of the window
+                    let weight = win.weight;
+                    win.weight = 1.0;
+                    (win.x, win.y, weight)
                 };
-                let mut new = WindowContainer::new_container(x, y, Some(win_payload));
+                let mut new =
+                    WindowContainer::new_container(self.backend.clone(), x, y, Some(win_payload));
                 new.direction = direction;
+                new.weight = weight;
                 self.payload.insert(self.focus,
                                     WindowPayload::Container(Rc::new(RefCell::new(new))));
             }
             _ => unreachable!(),
         }
     }
+    /// Opens a new window next to the focused one and focuses it. Fullscreen
+    /// mode ends first.
     pub fn split(&mut self) -> WindowRef {
         log(format!("{:?}", self));
-        let new_win_id = self.with_focused_container_mut(|f| {
-            let win = match f.direction {
-                WindowSplitDirection::Vertical => f.split_vertical(),
-                WindowSplitDirection::Horizontal => f.split_horizontal(),
-            };
-            let id = win.id;
+        if self.fullscreen.is_some() {
+            self.toggle_fullscreen();
+        }
+        // The window gets its real geometry when the tree is laid out
+        let (x, y, width, height) =
+            self.with_focused_container(|f| (f.container_x, f.container_y, f.width, f.height));
+        let win = Window::new_window(self.backend.clone(), x, y, (width, height));
+        let win = Rc::new(RefCell::new(win));
+        self.insert_window(win.clone());
+        self.floating_focused = false;
+        self.resize();
+        win
+    }
+    /// Puts a window after the focused window of the focused container.
+    fn insert_window(&mut self, win: WindowRef) {
+        self.with_focused_container_mut(|f| {
+            // New windows get an average sized slot
+            win.borrow_mut().weight = f.payload.iter().map(|pl| pl.weight()).sum::<f64>() /
+                                      f.payload.len() as f64;
             f.focus += 1;
-            f.payload.push(WindowPayload::Window(Rc::new(RefCell::new(win))));
-            id
-        });
-        WindowContainer::resize();
-        match self.find(new_win_id) {
-            Some(WindowPayload::Window(wref)) => wref.clone(),
-            _ => panic!("Could not find a window that as just splitted"),
+            f.payload.insert(f.focus, WindowPayload::Window(win.clone()));
+        })
+    }
+    /// Creates a window that floats above the tiled windows and focuses it.
+    pub fn new_floating(&mut self, x: i32, y: i32, width: i32, height: i32) -> WindowRef {
+        let win = Window::new_window(self.backend.clone(), x, y, (width, height));
+        let win = Rc::new(RefCell::new(win));
+        self.floating.push(win.clone());
+        self.floating_focused = true;
+        self.refresh_windows(false);
+        win
+    }
+    pub fn move_floating(&mut self, window: &WindowRef, x: i32, y: i32) {
+        if self.is_floating(window) {
+            {
+                let mut w = window.borrow_mut();
+                w.x = x;
+                w.y = y;
+                WindowContainer::reresize_window(&mut w);
+            }
+            self.resize();
+        }
+    }
+    pub fn resize_floating(&mut self, window: &WindowRef, width: i32, height: i32) {
+        if self.is_floating(window) {
+            {
+                let mut w = window.borrow_mut();
+                w.xmax = width;
+                w.ymax = height;
+                WindowContainer::reresize_window(&mut w);
+            }
+            self.resize();
         }
     }
+    /// Brings a floating window to the top. The topmost floating window is the
+    /// one that has the focus when floating windows are focused.
+    pub fn raise(&mut self, window: &WindowRef) {
+        if let Some(pos) = self.floating.iter().position(|w| w == window) {
+            let w = self.floating.remove(pos);
+            self.floating.push(w);
+            self.resize();
+        }
+    }
+    /// Moves the focused window from the tiled tree to the floating windows
+    /// or back.
+    pub fn toggle_floating(&mut self) {
+        if self.floating_focused {
+            let win = match self.floating.pop() {
+                Some(win) => win,
+                None => return,
+            };
+            win.borrow().set_z_order(0);
+            self.floating_focused = false;
+            self.insert_window(win);
+        } else {
+            let win = match self.take_focused() {
+                Some(win) => win,
+                None => return,
+            };
+            {
+                let (width, height) = self.backend.screen_size();
+                let mut w = win.borrow_mut();
+                w.x = width / 4;
+                w.y = height / 4;
+                w.xmax = width / 2;
+                w.ymax = height / 2;
+                WindowContainer::reresize_window(&mut w);
+            }
+            self.floating.push(win);
+            self.floating_focused = true;
+        }
+        self.resize();
+    }
+    /// Moves the focus between the tiled windows and the floating windows.
+    pub fn toggle_focus_mode(&mut self) {
+        self.floating_focused = !self.floating_focused && !self.floating.is_empty();
+        self.refresh_windows(false);
+    }
+    fn is_floating(&self, window: &WindowRef) -> bool {
+        self.floating.iter().any(|w| w == window)
+    }
     pub fn with_focused_container_mut<F, T>(&mut self, f: F) -> T
         where F: Fn(&mut WindowContainer) -> T
     {
         match self.focused_container() {
-            Some(w) => f(&mut *w.borrow_mut()),
+            Some(w) => f(&mut w.borrow_mut()),
             None => f(self),
         }
     }
@@ -395,55 +1010,16 @@ impl WindowContainer {
         where F: Fn(&WindowContainer) -> T
     {
         match self.focused_container() {
-            Some(w) => f(&*w.borrow()),
+            Some(w) => f(&w.borrow()),
             None => f(self),
         }
     }
-    fn with_parent_of<F, T>(&self, c: &WindowContainer, f: F) -> T
-        where F: Fn(&mut WindowContainer) -> T
-    {
-        fn find_first_parent(from: &WindowContainer, c: &WindowContainer) -> Option<ContainerRef> {
-            for pl in from.payload
-                .iter()
-                .filter(|pl| pl.is_container())
-                .map(|pl| pl.as_container()) {
-                if *c == *pl.borrow() {
-                    return None;
-                } else {
-                    find_parent(pl.clone(), c);
-                }
-            }
-            unreachable!();
-        }
-        fn find_parent(from: ContainerRef, c: &WindowContainer) -> Option<ContainerRef> {
-            for pl in from.borrow()
-                .payload
-                .iter()
-                .filter(|pl| pl.is_container())
-                .map(|pl| pl.as_container()) {
-                if *c == *pl.borrow() {
-                    return Some(from.clone());
-                } else {
-                    find_parent(pl.clone(), c);
-                }
-            }
-            unreachable!();
-        }
-        match find_first_parent(self, c) {
-            Some(parent) => f(&mut *parent.borrow_mut()),
-            None => unsafe {
-                let ref mut rc = *ROOT_CONTAINER.unwrap();
-                f(rc)
-            },
-        }
-    }
     fn focused_window(&self) -> WindowRef {
         self.payload[self.focus].as_window()
     }
     fn focused_container(&self) -> Option<ContainerRef> {
-        let ref win = self.payload[self.focus];
-        match win {
-            &WindowPayload::Container(ref container) => {
+        match self.payload[self.focus] {
+            WindowPayload::Container(ref container) => {
                 let c = container.borrow();
                 if c.payload[c.focus].is_container() {
                     c.focused_container()
@@ -451,104 +1027,133 @@ impl WindowContainer {
                     Some(container.clone())
                 }
             }
-            &WindowPayload::Window(_) => None,
+            WindowPayload::Window(_) => None,
         }
     }
-    fn calculate_dimensions(&self,
-                            window_count: Option<i32>,
-                            direction: Option<WindowSplitDirection>)
-                            -> (i32, i32, i32) {
-        // TODO: Not sure if this function makes any sense
-        let window_count = window_count.unwrap_or(self.payload.len() as i32);
-        let (dim, pos) = match direction.unwrap_or(self.direction) {
-            WindowSplitDirection::Horizontal => (self.height, self.container_y),
-            WindowSplitDirection::Vertical => (self.width, self.container_x),
-        };
-        let size = dim / window_count;
-        let rounding_error = dim % size;
-        (pos, size, rounding_error)
+    fn reresize_window(w: &mut Window) {
+        // Windows that are not at the left edge of the screen get a border
+        match (w.x > 0, w.border_win) {
+            (true, None) => w.border_win = Some(w.backend.create_surface(w.x, w.y, 1, w.ymax - 1)),
+            (false, Some(bwin)) => {
+                w.backend.destroy_surface(bwin);
+                w.border_win = None;
+            }
+            _ => {}
+        }
+        let b = &w.backend;
+        b.clear(w.win);
+        if let Some(bwin) = w.border_win {
+            b.resize_surface(w.win, w.xmax - 1, w.ymax - 1);
+            b.resize_surface(w.header_win, w.xmax, 1);
+            b.resize_surface(bwin, 1, w.ymax - 1);
+            b.move_surface(bwin, w.x, w.y);
+            b.move_surface(w.header_win, w.x, w.y + w.ymax - 1);
+            b.move_surface(w.win, w.x + 1, w.y);
+        } else {
+            b.resize_surface(w.win, w.xmax, w.ymax - 1);
+            b.resize_surface(w.header_win, w.xmax, 1);
+            b.move_surface(w.header_win, w.x, w.y + w.ymax - 1);
+            b.move_surface(w.win, w.x, w.y);
+        }
+        w.resize_terminal();
     }
-    fn refresh_dimensions(&mut self, dimensions: (i32, i32, i32)) {
-        let (pos, size, _) = dimensions;
-        for (i, window) in self.payload.iter_mut().enumerate() {
-            match window {
-                &mut WindowPayload::Window(ref w) => {
+    /// Shows the focused window over the whole screen, or goes back to the
+    /// layout that was shown before. The tiles cannot be changed while the
+    /// window is shown fullscreen, and opening a new window ends it.
+    pub fn toggle_fullscreen(&mut self) {
+        match self.fullscreen.take() {
+            Some((w, (x, y, width, height))) => {
+                {
                     let mut w = w.borrow_mut();
-                    w.cursor.set((0, 0));
-                    match self.direction {
-                        WindowSplitDirection::Horizontal => {
-                            w.y = pos + (i as i32) * size;
-                            w.ymax = size;
-                        }
-                        WindowSplitDirection::Vertical => {
-                            w.x = pos + (i as i32) * size;
-                            w.xmax = size;
-                        }
-                    }
-                    WindowContainer::reresize_window(&mut *w);
+                    w.x = x;
+                    w.y = y;
+                    w.xmax = width;
+                    w.ymax = height;
+                    WindowContainer::reresize_window(&mut w);
                 }
-                _ => {}
-            }
-        }
-    }
-    fn split_vertical(&mut self) -> Window {
-        let dim = self.calculate_dimensions(Some(self.payload.len() as i32 + 1), None);
-        self.refresh_dimensions(dim);
-        let (_, window_width, rounding_error) = dim;
-        let new_window_x = self.container_x + (self.payload.len() as i32 * window_width);
-        Window::new_window(new_window_x,
-                           self.container_y,
-                           (window_width + rounding_error, self.height),
-                           true)
-    }
-    fn split_horizontal(&mut self) -> Window {
-        let dim = self.calculate_dimensions(Some(self.payload.len() as i32 + 1), None);
-        self.refresh_dimensions(dim);
-        let (_, window_height, rounding_error) = dim;
-        let new_window_y = self.container_y + (self.payload.len() as i32 * window_height);
-        Window::new_window(self.container_x,
-                           new_window_y,
-                           (self.width, window_height + rounding_error),
-                           self.container_x > 0)
+                self.resize();
+            }
+            None => {
+                let w = self.focused();
+                let rect = {
+                    let w = w.borrow();
+                    (w.x, w.y, w.xmax, w.ymax)
+                };
+                self.fullscreen = Some((w, rect));
+                self.refresh_windows(true);
+            }
+        }
     }
-    fn reresize_window(w: &mut Window) {
-        wclear(w.win);
-        if let Some(bwin) = w.border_win {
-            wresize(w.win, w.ymax - 1, w.xmax - 1);
-            wresize(w.header_win, 1, w.xmax);
-            wresize(bwin, w.ymax - 1, 1);
-            mvwin(bwin, w.y, w.x);
-            mvwin(w.header_win, w.y + w.ymax - 1, w.x);
-            mvwin(w.win, w.y, w.x + 1);
-        } else {
-            wresize(w.win, w.ymax - 1, w.xmax);
-            wresize(w.header_win, 1, w.xmax);
-            mvwin(w.header_win, w.y + w.ymax - 1, w.x);
-            mvwin(w.win, w.y, w.x);
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen.is_some()
+    }
+    fn show_fullscreen(&mut self, w: &WindowRef) {
+        self.hide();
+        let (width, height) = self.backend.screen_size();
+        let mut w = w.borrow_mut();
+        if (w.x, w.y, w.xmax, w.ymax) != (0, 0, width, height) {
+            w.x = 0;
+            w.y = 0;
+            w.xmax = width;
+            w.ymax = height;
+            WindowContainer::reresize_window(&mut w);
         }
+        w.visible = true;
+        w.header_color = Color::StatusSelected;
+        w.draw(true);
     }
     fn refresh_windows(&mut self, reprint: bool) {
-        self.refresh_windows_internal(reprint, true)
+        if let Some((w, _)) = self.fullscreen.clone() {
+            // Closing the window ends the fullscreen mode
+            if self.find_window(w.borrow().id).is_some() {
+                self.show_fullscreen(&w);
+                return;
+            }
+            self.fullscreen = None;
+        }
+        let tiled_focused = !self.floating_focused;
+        self.refresh_windows_internal(reprint, tiled_focused);
+        self.refresh_floating(reprint);
+    }
+    fn refresh_floating(&mut self, reprint: bool) {
+        let count = self.floating.len();
+        for (i, w) in self.floating.iter().enumerate() {
+            let mut w = w.borrow_mut();
+            w.set_z_order(i as i32 + 1);
+            w.header_color = if self.floating_focused && i == count - 1 {
+                Color::StatusSelected
+            } else {
+                Color::Status
+            };
+            w.visible = true;
+            w.draw(reprint);
+        }
     }
     fn refresh_windows_internal(&mut self, reprint: bool, in_focus_chain: bool) {
+        let one_visible = self.direction.shows_one_child();
         for (i, window) in self.payload.iter_mut().enumerate() {
-            match window {
-                &mut WindowPayload::Window(ref w) => {
+            if one_visible && i != self.focus {
+                window.hide();
+                continue;
+            }
+            match *window {
+                WindowPayload::Window(ref w) => {
                     let mut w = w.borrow_mut();
-                    let header_color = {
+                    // Windows that were hidden have to be drawn from scratch
+                    let reprint = reprint || !w.visible;
+                    w.visible = true;
+                    w.header_color = {
                         let focused = in_focus_chain && self.focus == i;
-                        let color = if focused {
+                        if focused {
                             Color::StatusSelected
                         } else {
                             Color::Status
-                        };
-                        COLOR_PAIR(color.into())
+                        }
                     };
-                    wbkgd(w.header_win, header_color);
-                    if self.root {
+                    if !w.header_set && self.root {
                         w.header =
                             format!("Window {} ({}, {}) ({}, {})", i, w.x, w.y, w.xmax, w.ymax);
-                    } else {
+                    } else if !w.header_set {
                         w.header = format!("Container {} ({}, {}) ({}, {})",
                                            i,
                                            w.x,
@@ -556,223 +1161,349 @@ impl WindowContainer {
                                            w.xmax,
                                            w.ymax);
                     }
-                    w.print_header();
-                    if let Some(bwin) = w.border_win {
-                        mvwvline(bwin, 0, 0, ACS_HLINE(), 1000);
-                        wrefresh(bwin);
-                    }
-                    if reprint {
-                        w.cursor.set((0, 0));
-                        w.reprint_buffer();
-                    } else {
-                        wrefresh(w.win);
-                    }
+                    w.draw(reprint);
                 }
-                &mut WindowPayload::Container(ref c) => {
+                WindowPayload::Container(ref c) => {
                     let mut c = c.borrow_mut();
                     let in_focus_chain = in_focus_chain && self.focus == i;
                     c.refresh_windows_internal(reprint, in_focus_chain);
                 }
             }
         }
+        self.draw_titles();
     }
     pub fn set_header(&mut self, header: &str) {
-        self.with_focused_container_mut(|w| {
-            let w = w.payload[w.focus].as_window();
-            let mut w = w.borrow_mut();
-            w.header = header.to_owned();
-            w.print_header();
-        })
+        self.with_focused_window_mut(|w| w.set_header(header))
     }
-    fn on_resize(&mut self, first: bool, mut parent: (i32, i32, i32, i32)) {
-        fn window_filter(p: &mut WindowPayload) -> Option<WindowRef> {
-            if p.is_window() {
-                Some(p.as_window())
-            } else {
-                None
+    /// Lays out the container and everything in it inside the given
+    /// rectangle. Every payload entry gets a slot of its own, except in
+    /// tabbed and stacked containers where they all share the same one.
+    fn layout(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.container_x = x;
+        self.container_y = y;
+        self.width = width;
+        self.height = height;
+        self.layout_titles();
+        match self.direction {
+            WindowSplitDirection::Horizontal |
+            WindowSplitDirection::Vertical => {
+                let total = if self.direction == WindowSplitDirection::Horizontal {
+                    height
+                } else {
+                    width
+                };
+                let sizes = self.child_sizes(total);
+                let mut pos = 0;
+                for (pl, size) in self.payload.iter().zip(sizes) {
+                    if self.direction == WindowSplitDirection::Horizontal {
+                        pl.layout(x, y + pos, width, size);
+                    } else {
+                        pl.layout(x + pos, y, size, height);
+                    }
+                    pos += size;
+                }
             }
+            WindowSplitDirection::Tabbed |
+            WindowSplitDirection::Stacked => {
+                let bar = if self.direction == WindowSplitDirection::Tabbed {
+                    self.titles.len().min(1) as i32
+                } else {
+                    self.titles.len() as i32
+                };
+                for pl in self.payload.iter() {
+                    pl.layout(x, y + bar, width, height - bar);
+                }
+            }
+        }
+    }
+    fn layout_titles(&mut self) {
+        let rects = self.title_rects();
+        while self.titles.len() > rects.len() {
+            self.backend.destroy_surface(self.titles.pop().unwrap());
+        }
+        while self.titles.len() < rects.len() {
+            let surface = self.backend.create_surface(self.container_x, self.container_y, 1, 1);
+            self.titles.push(surface);
+        }
+        for (surface, &(x, y, width, height)) in self.titles.iter().zip(rects.iter()) {
+            self.backend.resize_surface(*surface, width, height);
+            self.backend.move_surface(*surface, x, y);
         }
-        fn container_filter(p: &mut WindowPayload) -> Option<ContainerRef> {
-            if p.is_container() {
-                Some(p.as_container())
+    }
+    /// Rectangles of the tabs or title rows as `(x, y, width, height)`. Tabs
+    /// and title rows that do not fit are left out, so the first children
+    /// get them.
+    fn title_rects(&self) -> Vec<(i32, i32, i32, i32)> {
+        match self.direction {
+            WindowSplitDirection::Tabbed => {
+                // Every tab is at least one cell wide
+                let count = self.payload.len().min(self.width.max(0) as usize);
+                let mut x = self.container_x;
+                distribute(self.width, &vec![1.0; count])
+                    .into_iter()
+                    .map(|width| {
+                        x += width;
+                        (x - width, self.container_y, width, 1)
+                    })
+                    .collect()
+            }
+            WindowSplitDirection::Stacked => {
+                // Title rows leave room for the content of the focused child
+                let rows = (self.height - MIN_WINDOW_SIZE).max(0);
+                let count = (self.payload.len() as i32).min(rows);
+                (0..count).map(|i| (self.container_x, self.container_y + i, self.width, 1))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+    fn draw_titles(&self) {
+        for (i, (surface, pl)) in self.titles.iter().zip(self.payload.iter()).enumerate() {
+            let color = if i == self.focus {
+                Color::StatusSelected
             } else {
-                None
+                Color::Status
+            };
+            self.backend.draw_header(*surface, 1, &pl.title(), color);
+            self.backend.refresh(*surface);
+        }
+    }
+    /// Changes the layout of the container the focused window is in.
+    pub fn set_layout(&mut self, layout: WindowSplitDirection) {
+        if self.fullscreen.is_some() {
+            return;
+        }
+        self.with_focused_container_mut(|c| c.direction = layout);
+        self.resize();
+    }
+    /// Splits `total` cells between the children according to their weights.
+    /// Rounding errors go to the last child.
+    fn child_sizes(&self, total: i32) -> Vec<i32> {
+        let weights = self.payload.iter().map(|pl| pl.weight()).collect::<Vec<_>>();
+        distribute(total, &weights)
+    }
+    /// Makes the focused window bigger in the given dimension, taking the
+    /// space from its siblings. The nearest container in the focus chain that
+    /// is split in that dimension is the one that gets resized.
+    pub fn grow(&mut self, dimension: Dimension, amount: ResizeAmount) {
+        if self.floating_focused || self.fullscreen.is_some() {
+            return;
+        }
+        if self.resize_focused(dimension.split_direction(), amount, true) {
+            self.resize();
+        }
+    }
+    /// Makes the focused window smaller in the given dimension, giving the
+    /// space to its siblings.
+    pub fn shrink(&mut self, dimension: Dimension, amount: ResizeAmount) {
+        if self.floating_focused || self.fullscreen.is_some() {
+            return;
+        }
+        if self.resize_focused(dimension.split_direction(), amount, false) {
+            self.resize();
+        }
+    }
+    fn resize_focused(&mut self,
+                      direction: WindowSplitDirection,
+                      amount: ResizeAmount,
+                      grow: bool)
+                      -> bool {
+        if self.payload[self.focus].is_container() {
+            let c = self.payload[self.focus].as_container();
+            if c.borrow_mut().resize_focused(direction, amount, grow) {
+                return true;
             }
         }
+        if self.direction != direction || self.payload.len() < 2 {
+            return false;
+        }
+        let total = if self.direction == WindowSplitDirection::Horizontal {
+            self.height
+        } else {
+            self.width
+        };
+        let others = self.payload.len() as i32 - 1;
+        let max_size = total - others * MIN_WINDOW_SIZE;
+        if max_size < MIN_WINDOW_SIZE {
+            return true;
+        }
+        let delta = match amount {
+            ResizeAmount::Cells(cells) => cells,
+            ResizeAmount::Percent(percent) => total * percent / 100,
+        };
+        let size = self.child_sizes(total)[self.focus];
+        let new_size = if grow { size + delta } else { size - delta };
+        let new_size = new_size.max(MIN_WINDOW_SIZE).min(max_size);
 
-        if first {
-            // One more ugly hack
-            let mut h = 0;
-            let mut w = 0;
-            getmaxyx(stdscr, &mut h, &mut w);
-            self.height = h;
-            self.width = w;
-            parent = (self.width, self.height, 0, 0);
-        }
-        let windows_len = self.payload
-            .iter_mut()
-            .filter_map(window_filter)
-            .size_hint()
-            .1
-            .unwrap();
-
-        let containers_len = self.payload
-            .iter_mut()
-            .filter_map(container_filter)
-            .size_hint()
-            .1
-            .unwrap();
-        let (cpos_h, csize_h, crounding_error_h) =
-            self.calculate_dimensions(Some(containers_len as i32),
-                                      Some(WindowSplitDirection::Horizontal));
-        let (cpos_v, csize_v, crounding_error_v) =
-            self.calculate_dimensions(Some(containers_len as i32),
-                                      Some(WindowSplitDirection::Vertical));
-        let (wpos_h, wsize_h, wrounding_error_h) =
-            self.calculate_dimensions(Some(windows_len as i32),
-                                      Some(WindowSplitDirection::Horizontal));
-        let (wpos_v, wsize_v, wrounding_error_v) =
-            self.calculate_dimensions(Some(windows_len as i32),
-                                      Some(WindowSplitDirection::Vertical));
-
-        for (i, pl) in self.payload.iter_mut().enumerate() {
-            if pl.is_container() {
-                let pl = pl.as_container();
-                let mut container = pl.borrow_mut();
-                match self.direction {
-                    WindowSplitDirection::Horizontal => {
-                        let newh = csize_h +
-                                   if i == (containers_len - 1) {
-                            crounding_error_h
-                        } else {
-                            0
-                        };
-                        let newy = cpos_h + (i * csize_h as usize) as i32;
-                        container.container_x = parent.2;
-                        container.width = parent.0;
-                        container.container_y = newy;
-                        container.height = newh;
-                    }
-                    WindowSplitDirection::Vertical => {
-                        let neww = csize_v +
-                                   if i == (containers_len - 1) {
-                            crounding_error_v
-                        } else {
-                            0
-                        };
-                        let newx = cpos_v + (i * csize_v as usize) as i32;
-                        container.height = parent.1;
-                        container.container_y = parent.3;
-                        container.container_x = newx;
-                        container.width = neww;
-                    }
-                }
-                let p = (container.width,
-                         container.height,
-                         container.container_x,
-                         container.container_y);
-                container.on_resize(false, p);
+        let sum = self.payload.iter().map(|pl| pl.weight()).sum::<f64>();
+        let old_weight = self.payload[self.focus].weight();
+        let new_weight = sum * new_size as f64 / total as f64;
+        let scale = (sum - new_weight) / (sum - old_weight);
+        for (i, pl) in self.payload.iter().enumerate() {
+            if i == self.focus {
+                pl.set_weight(new_weight);
             } else {
-                let win = pl.as_window();
-                let mut win = win.borrow_mut();
-                match self.direction {
-                    WindowSplitDirection::Horizontal => {
-                        let newy = wpos_h + (i * wsize_h as usize) as i32;
-                        let newymax = wsize_h +
-                                      if i == (windows_len - 1) {
-                            wrounding_error_h
-                        } else {
-                            0
-                        };
-                        win.y = newy;
-                        win.ymax = newymax;
-                        win.x = self.container_x;
-                        win.xmax = self.width;
-                    }
-                    WindowSplitDirection::Vertical => {
-                        let newx = wpos_v + (i * wsize_v as usize) as i32;
-                        let newxmax = wsize_v +
-                                      if i == (windows_len - 1) {
-                            wrounding_error_v
-                        } else {
-                            0
-                        };
-                        win.x = newx;
-                        win.xmax = newxmax;
-                        win.y = self.container_y;
-                        win.ymax = self.height;
-                    }
-                }
-                WindowContainer::reresize_window(&mut *win);
+                pl.set_weight(pl.weight() * scale);
             }
         }
+        true
     }
-    pub fn wait_for_key(&self) -> i32 {
-        let resize_needed = ::std::cell::Cell::new(false);
-        let ret = self.with_focused_container(|f| {
-            let w = f.focused_window();
-            let ret = ncurses::wgetch(w.borrow().win);
-            if ret == RESIZE {
-                resize_needed.set(true);
-            }
-            ret
-        });
-        if resize_needed.get() {
-            WindowContainer::resize();
+    /// Returns the next key code, or -1 if there is none. This only reads
+    /// input: keys are not given to terminal panes, and the output of
+    /// terminal panes, commands and `WindowSender`s is not shown. Use
+    /// `Tcwm::next_event` or `Tcwm::run` with those.
+    pub fn wait_for_key(&mut self) -> i32 {
+        let ret = {
+            let w = self.focused();
+            let w = w.borrow();
+            w.backend.get_key(w.win)
+        };
+        if ret == RESIZE {
+            self.resize();
         }
         ret
     }
-    pub fn resize() {
-        unsafe {
-            let ref mut rc = *ROOT_CONTAINER.unwrap();
-            rc.on_resize(true, (0, 0, 0, 0));
-            rc.refresh_windows(true);
+    pub fn resize(&mut self) {
+        let (width, height) = self.backend.screen_size();
+        self.layout(0, 0, width, height);
+        self.refresh_windows(true);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dimension {
+    Width,
+    Height,
+}
+impl Dimension {
+    fn split_direction(&self) -> WindowSplitDirection {
+        match *self {
+            Dimension::Width => WindowSplitDirection::Vertical,
+            Dimension::Height => WindowSplitDirection::Horizontal,
         }
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResizeAmount {
+    Cells(i32),
+    /// Percentage of the size of the container being resized.
+    Percent(i32),
+}
+
+#[derive(Debug)]
 pub enum CursesError {
     CursesAlreadyInitialized,
+    /// A saved layout could not be parsed or does not describe a valid tree.
+    InvalidLayout(String),
+    /// The window has been closed.
+    WindowNotFound(Id),
+    /// The window is the last tiled window of its workspace.
+    LastWindow(Id),
+    /// Starting the program of a terminal pane or command failed.
+    Io(::std::io::Error),
+}
+impl fmt::Display for CursesError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CursesError::CursesAlreadyInitialized => write!(fmt, "curses is already in use"),
+            CursesError::InvalidLayout(ref reason) => write!(fmt, "invalid layout: {}", reason),
+            CursesError::WindowNotFound(id) => write!(fmt, "window {} not found", id),
+            CursesError::LastWindow(id) => write!(fmt, "window {} is the last tiled window", id),
+            CursesError::Io(ref err) => write!(fmt, "{}", err),
+        }
+    }
+}
+impl error::Error for CursesError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            CursesError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum WindowSplitDirection {
     Horizontal,
     Vertical,
+    /// Only the focused child is shown, with a tab bar for all children.
+    Tabbed,
+    /// Only the focused child is shown, below a title row for every child.
+    Stacked,
 }
 impl WindowSplitDirection {
     fn direction_ok(&self, dir: Direction) -> bool {
         match *self {
-            WindowSplitDirection::Vertical => dir == Direction::Left || dir == Direction::Right,
-            WindowSplitDirection::Horizontal => dir == Direction::Up || dir == Direction::Down,
+            WindowSplitDirection::Vertical |
+            WindowSplitDirection::Tabbed => dir == Direction::Left || dir == Direction::Right,
+            WindowSplitDirection::Horizontal |
+            WindowSplitDirection::Stacked => dir == Direction::Up || dir == Direction::Down,
         }
     }
+    fn shows_one_child(&self) -> bool {
+        matches!(*self, WindowSplitDirection::Tabbed | WindowSplitDirection::Stacked)
+    }
 }
 
-enum Color {
-    Default = 1,
-    Selection = 2,
-    Status = 3,
-    StatusSelected = 4,
+/// Length of the overlap of two ranges given as start and length.
+fn overlap(a: i32, a_len: i32, b: i32, b_len: i32) -> i32 {
+    (a + a_len).min(b + b_len) - a.max(b)
 }
-impl Into<i16> for Color {
-    fn into(self) -> i16 {
-        self as i16
-    }
+
+/// Splits `total` cells according to `weights`. Rounding errors go to the
+/// last slot.
+fn distribute(total: i32, weights: &[f64]) -> Vec<i32> {
+    let sum = weights.iter().sum::<f64>();
+    let mut acc = 0.0;
+    let mut prev = 0;
+    weights.iter()
+        .enumerate()
+        .map(|(i, weight)| {
+            acc += weight;
+            let end = if i == weights.len() - 1 {
+                total
+            } else {
+                (total as f64 * acc / sum + 1e-6).floor() as i32
+            };
+            let size = end - prev;
+            prev = end;
+            size
+        })
+        .collect()
 }
 
 pub struct Window {
     id: Id,
-    win: WINDOW,
-    border_win: Option<WINDOW>,
-    header_win: WINDOW,
+    backend: BackendRef,
+    win: Surface,
+    border_win: Option<Surface>,
+    header_win: Surface,
     x: i32,
     y: i32,
     xmax: i32,
     ymax: i32,
     cursor: Cell<(i32, i32)>,
-    lines: Vec<String>,
+    lines: VecDeque<StyledText>,
+    scrollback: usize,
+    scroll: usize,
+    overwritable: bool,
+    word_wrap: bool,
+    // Interprets the output given to write
+    output: AnsiParser,
+    // Whether the last line of the buffer is the unfinished line of output
+    partial: bool,
+    terminal: Option<terminal::Terminal>,
+    process: Option<process::Process>,
+    // Hidden windows keep their buffers but do not touch the screen
+    visible: bool,
     header: String,
+    // Headers set by the user are not replaced by the generated ones
+    header_set: bool,
+    header_color: Color,
+    urgent: bool,
+    weight: f64,
+    input_handler: Option<Box<dyn InputHandler>>,
 }
 impl PartialEq for Window {
     fn eq(&self, rhs: &Window) -> bool {
@@ -781,46 +1512,80 @@ impl PartialEq for Window {
 }
 impl Eq for Window {}
 impl Window {
-    fn new_window(x: i32, y: i32, dimensions: (i32, i32), border: bool) -> Window {
+    fn new_window(backend: BackendRef, x: i32, y: i32, dimensions: (i32, i32)) -> Window {
         let (xmax, ymax) = dimensions;
+        let border = x > 0;
+        // The same geometry as reresize_window gives
         let bwin = if border {
-            Some(newwin(ymax, 1, y, x))
+            Some(backend.create_surface(x, y, 1, ymax - 1))
         } else {
             None
         };
         let win = if border {
-            newwin(ymax - 1, xmax, y, x + 1)
+            backend.create_surface(x + 1, y, xmax - 1, ymax - 1)
         } else {
-            newwin(ymax - 1, xmax, y, x)
+            backend.create_surface(x, y, xmax, ymax - 1)
         };
-        let hwin = newwin(1, xmax, y + ymax - 1, x);
-        wbkgd(hwin, COLOR_PAIR(Color::StatusSelected.into()));
-        nodelay(win, true);
+        let hwin = backend.create_surface(x, y + ymax - 1, xmax, 1);
         Window {
             id: uuid::Uuid::new_v4(),
-            win: win,
+            backend,
+            win,
             border_win: bwin,
             header_win: hwin,
-            x: x,
-            y: y,
-            xmax: xmax,
-            ymax: ymax,
+            x,
+            y,
+            xmax,
+            ymax,
             cursor: Cell::new((0, 0)),
-            lines: vec![],
+            lines: VecDeque::new(),
+            scrollback: DEFAULT_SCROLLBACK,
+            scroll: 0,
+            overwritable: false,
+            word_wrap: false,
+            output: AnsiParser::new(false),
+            partial: false,
+            terminal: None,
+            process: None,
+            visible: true,
             header: "New window".into(),
+            header_set: false,
+            header_color: Color::StatusSelected,
+            urgent: false,
+            weight: 1.0,
+            input_handler: None,
         }
     }
-    fn new() -> Window {
-        let mut xmax = 0;
-        let mut ymax = 0;
-        getmaxyx(stdscr, &mut ymax, &mut xmax);
-        Window::new_window(0, 0, (xmax, ymax), false)
+    fn new(backend: BackendRef) -> Window {
+        let (xmax, ymax) = backend.screen_size();
+        Window::new_window(backend, 0, 0, (xmax, ymax))
     }
-    fn reprint_buffer(&mut self) {
-        for line in self.lines.iter() {
-            self.print_internal(line);
+    pub fn id(&self) -> Id {
+        self.id
+    }
+    /// Draws the header, the border and the contents of the window.
+    fn draw(&mut self, reprint: bool) {
+        self.print_header();
+        if let Some(bwin) = self.border_win {
+            self.backend.draw_border(bwin);
+            self.backend.refresh(bwin);
+        }
+        if reprint {
+            self.reprint_buffer();
+        } else {
+            self.backend.refresh(self.win);
+        }
+    }
+    fn set_z_order(&self, z: i32) {
+        self.backend.set_z_order(self.win, z);
+        self.backend.set_z_order(self.header_win, z);
+        if let Some(bwin) = self.border_win {
+            self.backend.set_z_order(bwin, z);
         }
-        wrefresh(self.win);
+    }
+    fn reprint_buffer(&mut self) {
+        self.scroll = self.scroll.min(self.max_scroll());
+        self.redraw();
     }
     fn print_header(&mut self) {
         let margin = if self.border_win.is_some() {
@@ -828,43 +1593,287 @@ impl Window {
         } else {
             1
         };
-        mvwprintw(self.header_win, 0, margin, &self.header[..]);
-        wrefresh(self.header_win);
+        let color = if self.urgent && self.header_color == Color::Status {
+            Color::Urgent
+        } else {
+            self.header_color
+        };
+        self.backend.draw_header(self.header_win, margin, &self.header, color);
+        if self.visible {
+            self.backend.refresh(self.header_win);
+        }
     }
-    fn print_internal(&self, s: &str) {
+    fn refresh(&self) {
+        if self.visible {
+            self.backend.refresh(self.win);
+        }
+    }
+    fn content_width(&self) -> i32 {
+        if self.border_win.is_some() {
+            self.xmax - 1
+        } else {
+            self.xmax
+        }
+    }
+    fn content_height(&self) -> i32 {
+        self.ymax - 1
+    }
+    /// Splits a line into the rows it takes on the screen.
+    fn wrap(&self, line: &StyledText) -> Vec<StyledText> {
+        let width = self.content_width().max(1) as usize;
+        if self.word_wrap {
+            line.wrap_words(width)
+        } else {
+            line.wrap(width)
+        }
+    }
+    fn row_count(&self) -> usize {
+        self.lines.iter().map(|line| self.wrap(line).len()).sum()
+    }
+    fn max_scroll(&self) -> usize {
+        self.row_count().saturating_sub(self.content_height().max(0) as usize)
+    }
+    /// Draws the rows that are visible with the current scroll position.
+    fn redraw(&self) {
+        let height = self.content_height().max(0) as usize;
+        let mut rows = vec![];
+        for line in self.lines.iter().rev() {
+            rows.extend(self.wrap(line).into_iter().rev());
+            if rows.len() >= height + self.scroll {
+                break;
+            }
+        }
+        let visible = rows.into_iter().skip(self.scroll).take(height).collect::<Vec<_>>();
+        for y in 0..height as i32 {
+            self.backend.clear_to_eol(self.win, 0, y);
+        }
+        for (y, row) in visible.iter().rev().enumerate() {
+            self.print_row(0, y as i32, row);
+        }
+        self.cursor.set((0, visible.len() as i32));
+        self.refresh();
+    }
+    fn print_internal(&self, text: &StyledText) {
         let (x, mut y) = self.cursor.get();
-        if y >= self.ymax {
-            // TODO: Scroll
-            return;
+        for row in self.wrap(text) {
+            self.print_row(x, y, &row);
+            y += 1;
         }
-        mvwprintw(self.win, y, x, s);
-        y += (s.len() as i32 / (self.xmax - 1)) + 1;
         self.cursor.set((x, y));
     }
-    pub fn print(&mut self, s: &str) {
-        self.print_internal(s);
-        self.lines.push(s.into());
-        wrefresh(self.win);
+    fn print_row(&self, mut x: i32, y: i32, row: &StyledText) {
+        for span in row.spans.iter() {
+            self.backend.print_styled(self.win, x, y, &span.text, span.style);
+            x += display_width(&span.text) as i32;
+        }
+    }
+    fn push_line(&mut self, text: StyledText) {
+        self.lines.push_back(text);
+        while self.lines.len() > self.scrollback {
+            self.lines.pop_front();
+        }
     }
-    pub fn print_overwriting(&mut self, s: &str) {
+    /// Prints a line. In windows with the ANSI interpreter turned on, the text
+    /// goes through `write`.
+    pub fn print<T>(&mut self, text: T)
+        where T: Into<StyledText>
+    {
+        let text = text.into();
+        self.end_partial();
+        // Text that already has styles is printed as it is
+        let plain = text.spans.iter().all(|span| span.style == Style::default());
+        if self.output.escapes() && plain {
+            self.write(&(text.text() + "\n"));
+        } else {
+            self.print_line(text);
+        }
+    }
+    /// Keeps the unfinished line of `write` as it is shown, so that the next
+    /// output starts on a new line.
+    fn end_partial(&mut self) {
+        if self.partial {
+            self.output.clear_line();
+            self.partial = false;
+            self.overwritable = false;
+        }
+    }
+    fn print_line(&mut self, text: StyledText) {
+        let rows = self.wrap(&text).len();
+        self.push_line(text.clone());
+        self.overwritable = false;
+        if self.scroll > 0 {
+            // Keep showing the same lines while the user is reading history
+            self.scroll = (self.scroll + rows).min(self.max_scroll());
+            return;
+        }
         let (_, y) = self.cursor.get();
-        self.cursor.set((0, y));
-        wmove(self.win, y, 0);
-        wclrtoeol(self.win);
-
-        // TODO: Scroll?
-        wprintw(self.win, s);
-
-        self.lines.pop();
-        self.lines.push(s.into());
-        wrefresh(self.win);
+        if y as usize + rows <= self.content_height().max(0) as usize {
+            self.print_internal(&text);
+            self.refresh();
+        } else {
+            self.redraw();
+        }
+    }
+    /// Prints a line that replaces the previous line printed with
+    /// `print_overwriting`, which is handy for progress indicators.
+    pub fn print_overwriting<T>(&mut self, text: T)
+        where T: Into<StyledText>
+    {
+        if self.overwritable {
+            self.lines.pop_back();
+        }
+        self.push_line(text.into());
+        self.overwritable = true;
+        if self.scroll == 0 {
+            self.redraw();
+        }
+    }
+    /// Writes output that can hold several lines or end in the middle of one.
+    /// Carriage return rewrites the unfinished line, which is shown right
+    /// away.
+    pub fn write(&mut self, s: &str) {
+        for line in self.output.feed(s) {
+            if self.partial {
+                self.print_overwriting(line);
+                self.overwritable = false;
+                self.partial = false;
+            } else {
+                self.print_line(line);
+            }
+        }
+        let line = self.output.line();
+        if !line.is_empty() || self.partial {
+            if !self.partial {
+                // Do not replace a line printed with print_overwriting
+                self.overwritable = false;
+            }
+            self.print_overwriting(line);
+            self.partial = true;
+        }
+    }
+    /// Turns interpreting ANSI escape sequences in `print` and `write` on or
+    /// off. SGR sequences become styles of the text.
+    pub fn set_ansi(&mut self, enabled: bool) {
+        self.output.set_escapes(enabled);
+    }
+    pub fn set_header(&mut self, header: &str) {
+        self.header = header.to_owned();
+        self.header_set = true;
+        self.print_header();
+    }
+    /// Marks the window as needing attention. Urgent windows get the urgent
+    /// header colors while they are not focused.
+    pub fn set_urgent(&mut self, urgent: bool) {
+        self.urgent = urgent;
+        self.print_header();
+    }
+    pub fn is_urgent(&self) -> bool {
+        self.urgent
+    }
+    /// Sends the keys this window gets while focused to `handler`.
+    pub fn set_input_handler<H>(&mut self, mut handler: H)
+        where H: InputHandler + 'static
+    {
+        handler.attach(self);
+        self.input_handler = Some(Box::new(handler));
+    }
+    pub fn clear_input_handler(&mut self) {
+        self.input_handler = None;
+    }
+    /// Shows new output of a terminal pane.
+    fn update_terminal(&mut self) {
+        let (lines, exit) = match self.terminal {
+            Some(ref mut t) => {
+                if !t.read() {
+                    return;
+                }
+                (t.lines(), t.exit_status())
+            }
+            None => return,
+        };
+        self.lines = lines.into_iter().collect();
+        self.scroll = 0;
+        self.overwritable = false;
+        self.redraw();
+        if let Some(status) = exit {
+            self.show_exit_status(status);
+        }
+    }
+    /// Adds the exit status of the program of the window to the header.
+    fn show_exit_status(&mut self, status: ::std::process::ExitStatus) {
+        let header = format!("{} ({})", self.header, status);
+        self.set_header(&header);
+    }
+    /// Exit status of the program of a terminal pane or command window that
+    /// has finished.
+    pub fn exit_status(&self) -> Option<::std::process::ExitStatus> {
+        match self.terminal {
+            Some(ref t) => t.exit_status(),
+            None => self.process.as_ref().and_then(|p| p.exit_status()),
+        }
+    }
+    /// Sets the size of the pty of a terminal pane to the size of the window.
+    fn resize_terminal(&mut self) {
+        let (cols, rows) = (self.content_width().max(1), self.content_height().max(1));
+        if let Some(ref mut t) = self.terminal {
+            t.resize(cols as u16, rows as u16);
+            self.lines = t.lines().into_iter().collect();
+        }
+    }
+    /// Wraps long lines at word boundaries instead of at the last cell that
+    /// fits.
+    pub fn set_word_wrap(&mut self, enabled: bool) {
+        self.word_wrap = enabled;
+        self.reprint_buffer();
+    }
+    /// Sets how many lines of output are kept. Older lines are dropped.
+    pub fn set_scrollback(&mut self, lines: usize) {
+        self.scrollback = lines.max(1);
+        while self.lines.len() > self.scrollback {
+            self.lines.pop_front();
+        }
+        self.reprint_buffer();
+    }
+    /// Scrolls `rows` rows towards older output. The window stops following
+    /// new output until it is scrolled back to the bottom.
+    pub fn scroll_up(&mut self, rows: usize) {
+        self.scroll = (self.scroll + rows).min(self.max_scroll());
+        self.redraw();
+    }
+    pub fn scroll_down(&mut self, rows: usize) {
+        self.scroll = self.scroll.saturating_sub(rows);
+        self.redraw();
+    }
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll = 0;
+        self.redraw();
+    }
+    pub fn page_up(&mut self) {
+        let rows = self.content_height().max(1) as usize;
+        self.scroll_up(rows);
+    }
+    pub fn page_down(&mut self) {
+        let rows = self.content_height().max(1) as usize;
+        self.scroll_down(rows);
+    }
+    /// Whether the window is showing the newest output.
+    pub fn is_following(&self) -> bool {
+        self.scroll == 0
+    }
+}
+impl Drop for WindowContainer {
+    fn drop(&mut self) {
+        for surface in self.titles.drain(..) {
+            self.backend.destroy_surface(surface);
+        }
     }
 }
 impl Drop for Window {
     fn drop(&mut self) {
-        delwin(self.win);
+        self.backend.destroy_surface(self.win);
+        self.backend.destroy_surface(self.header_win);
         if let Some(bwin) = self.border_win {
-            delwin(bwin);
+            self.backend.destroy_surface(bwin);
         }
     }
 }
@@ -0,0 +1,202 @@
+//! Mouse handling.
+//!
+//! Pressing the button inside a window focuses it, and pressing on a tab or a
+//! title row selects it. Dragging the border to the left of a window or the
+//! header at its bottom moves the edge between the two neighbouring splits.
+
+use {Dimension, Event, Id, MouseEvent, MouseKind, Tcwm, WindowContainer, WindowPayload,
+     WindowRef, WindowSplitDirection, MIN_WINDOW_SIZE};
+
+fn contains(rect: (i32, i32, i32, i32), x: i32, y: i32) -> bool {
+    let (rx, ry, width, height) = rect;
+    x >= rx && x < rx + width && y >= ry && y < ry + height
+}
+
+impl Tcwm {
+    /// Turns mouse handling on or off. It is off until this is called, so the
+    /// terminal keeps its own text selection.
+    pub fn set_mouse(&mut self, enabled: bool) {
+        self.backend.enable_mouse(enabled);
+    }
+    /// Acts on a mouse event. Events the manager does not use are returned.
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) -> Option<Event> {
+        match mouse.kind {
+            MouseKind::Press => {
+                // Windows drawn over the tiles get the click before the tabs
+                // and title rows under them
+                let on_top = match self.fullscreen {
+                    Some((ref w, _)) => Some(w.clone()),
+                    None => self.floating_at(mouse.x, mouse.y),
+                };
+                if on_top.is_none() && self.select_title(mouse.x, mouse.y) {
+                    return None;
+                }
+                let w = match on_top {
+                    Some(w) => w,
+                    None => self.window_at(mouse.x, mouse.y)?,
+                };
+                let id = w.borrow().id();
+                self.track_changes(|wm| {
+                    let _ = wm.focus_window(id);
+                });
+                if self.is_floating(&w) || self.fullscreen.is_some() {
+                    return Some(Event::Mouse(mouse));
+                }
+                let w = w.borrow();
+                if w.border_win.is_some() && mouse.x == w.x {
+                    self.drag = Some((id, Dimension::Width));
+                } else if mouse.y == w.y + w.ymax - 1 {
+                    self.drag = Some((id, Dimension::Height));
+                } else {
+                    return Some(Event::Mouse(mouse));
+                }
+                None
+            }
+            MouseKind::Drag => {
+                let (id, dimension) = match self.drag {
+                    Some(drag) => drag,
+                    None => return Some(Event::Mouse(mouse)),
+                };
+                let pos = match dimension {
+                    Dimension::Width => mouse.x,
+                    // The header is the last row above the edge
+                    Dimension::Height => mouse.y + 1,
+                };
+                if self.move_edge(id, dimension, pos) {
+                    self.resize();
+                }
+                None
+            }
+            MouseKind::Release => {
+                match self.drag.take() {
+                    Some(_) => None,
+                    None => Some(Event::Mouse(mouse)),
+                }
+            }
+        }
+    }
+    fn select_title(&mut self, x: i32, y: i32) -> bool {
+        let mut selected = false;
+        self.track_changes(|wm| {
+            if wm.click_title(x, y) {
+                wm.floating_focused = false;
+                wm.resize();
+                selected = true;
+            }
+        });
+        selected
+    }
+    /// The window on top at screen position `x`, `y`.
+    fn window_at(&self, x: i32, y: i32) -> Option<WindowRef> {
+        self.floating_at(x, y).or_else(|| visible_at(self.all_windows().iter(), x, y))
+    }
+    /// The topmost floating window at screen position `x`, `y`.
+    fn floating_at(&self, x: i32, y: i32) -> Option<WindowRef> {
+        visible_at(self.floating.iter().rev(), x, y)
+    }
+}
+
+/// The first of `windows` that is shown at `x`, `y`.
+fn visible_at<'a, I>(mut windows: I, x: i32, y: i32) -> Option<WindowRef>
+    where I: Iterator<Item = &'a WindowRef>
+{
+    windows.find(|w| {
+            let w = w.borrow();
+            w.visible && contains((w.x, w.y, w.xmax, w.ymax), x, y)
+        })
+        .cloned()
+}
+
+impl WindowPayload {
+    fn rect(&self) -> (i32, i32, i32, i32) {
+        match *self {
+            WindowPayload::Window(ref w) => {
+                let w = w.borrow();
+                (w.x, w.y, w.xmax, w.ymax)
+            }
+            WindowPayload::Container(ref c) => {
+                let c = c.borrow();
+                (c.container_x, c.container_y, c.width, c.height)
+            }
+        }
+    }
+    fn contains_window(&self, id: Id) -> bool {
+        match *self {
+            WindowPayload::Window(ref w) => w.borrow().id == id,
+            WindowPayload::Container(ref c) => c.borrow().find(id).is_some(),
+        }
+    }
+}
+
+impl WindowContainer {
+    /// Selects the tab or title row at `x`, `y` and points the focus of the
+    /// containers above it towards it.
+    fn click_title(&mut self, x: i32, y: i32) -> bool {
+        if let Some(i) = self.title_rects().into_iter().position(|r| contains(r, x, y)) {
+            self.focus = i;
+            return true;
+        }
+        let one_visible = self.direction.shows_one_child();
+        for (i, pl) in self.payload.iter().enumerate() {
+            if one_visible && i != self.focus {
+                continue;
+            }
+            if let WindowPayload::Container(ref c) = *pl {
+                if c.borrow_mut().click_title(x, y) {
+                    self.focus = i;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+    /// Moves the edge that starts at the window `id` (the left edge for
+    /// `Width`, the bottom edge for `Height`) to `pos`. The innermost
+    /// container that has such an edge is the one that changes.
+    fn move_edge(&mut self, id: Id, dimension: Dimension, pos: i32) -> bool {
+        let i = match self.payload.iter().position(|pl| pl.contains_window(id)) {
+            Some(i) => i,
+            None => return false,
+        };
+        if let WindowPayload::Container(ref c) = self.payload[i] {
+            if c.borrow_mut().move_edge(id, dimension, pos) {
+                return true;
+            }
+        }
+        let direction = dimension.split_direction();
+        if self.direction != direction {
+            return false;
+        }
+        let (x, y, ymax) = match self.find(id) {
+            Some(WindowPayload::Window(w)) => {
+                let w = w.borrow();
+                (w.x, w.y, w.ymax)
+            }
+            _ => return false,
+        };
+        let (sx, sy, _, sheight) = self.payload[i].rect();
+        // The two slots on either side of the edge
+        let first = match dimension {
+            Dimension::Width if i > 0 && sx == x => i - 1,
+            Dimension::Height if i + 1 < self.payload.len() && sy + sheight == y + ymax => i,
+            _ => return false,
+        };
+        let (total, start) = if direction == WindowSplitDirection::Horizontal {
+            (self.height, self.container_y)
+        } else {
+            (self.width, self.container_x)
+        };
+        let mut sizes = self.child_sizes(total);
+        let first_start = start + sizes[..first].iter().sum::<i32>();
+        let both = sizes[first] + sizes[first + 1];
+        if both < 2 * MIN_WINDOW_SIZE {
+            return true;
+        }
+        sizes[first] = (pos - first_start).max(MIN_WINDOW_SIZE).min(both - MIN_WINDOW_SIZE);
+        sizes[first + 1] = both - sizes[first];
+        for (pl, size) in self.payload.iter().zip(sizes) {
+            pl.set_weight(size as f64);
+        }
+        true
+    }
+}
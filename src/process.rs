@@ -0,0 +1,153 @@
+//! Captured command output.
+//!
+//! `Tcwm::run_command` starts a program with its stdout and stderr connected
+//! to pipes and streams what it writes into a new window, one whole line at
+//! a time from each pipe. The pipes are read without blocking whenever the
+//! event loop polls for input, so a command that prints slowly does not hold
+//! up key handling.
+
+use std::io::{self, Read};
+use std::os::unix::io::AsRawFd;
+use std::process::{Child, Command, ExitStatus, Stdio};
+
+use libc;
+
+use {CursesError, Id, Tcwm, Window};
+
+pub struct Process {
+    child: Child,
+    // Pipes that have not reached end of file yet, with the line each one
+    // has not finished
+    pipes: Vec<(Box<dyn Read>, Vec<u8>)>,
+    exit: Option<ExitStatus>,
+}
+impl Process {
+    pub fn spawn(command: &mut Command) -> io::Result<Process> {
+        let mut child = command.stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let mut pipes: Vec<(Box<dyn Read>, Vec<u8>)> = vec![];
+        if let Some(stdout) = child.stdout.take() {
+            set_nonblocking(stdout.as_raw_fd());
+            pipes.push((Box::new(stdout), vec![]));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            set_nonblocking(stderr.as_raw_fd());
+            pipes.push((Box::new(stderr), vec![]));
+        }
+        Ok(Process {
+            child,
+            pipes,
+            exit: None,
+        })
+    }
+    /// Reads the lines that are available. Lines of stdout and stderr are
+    /// passed on whole, so they do not get mixed.
+    pub fn read(&mut self) -> String {
+        let mut output = String::new();
+        let mut buf = [0; 4096];
+        self.pipes.retain_mut(|&mut (ref mut pipe, ref mut rest)| {
+            let open = loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) => break false,
+                    Ok(n) => rest.extend_from_slice(&buf[..n]),
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break true,
+                    Err(_) => break false,
+                }
+            };
+            let end = if open {
+                rest.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1)
+            } else {
+                rest.len()
+            };
+            output.push_str(&String::from_utf8_lossy(&rest[..end]));
+            // The last line is finished at end of file
+            if !open && !rest.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            rest.drain(..end);
+            open
+        });
+        if self.pipes.is_empty() && self.exit.is_none() {
+            self.exit = self.child.try_wait().ok().and_then(|status| status);
+        }
+        output
+    }
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit
+    }
+}
+impl Drop for Process {
+    fn drop(&mut self) {
+        if self.exit.is_none() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/// The program and arguments of `command` separated by spaces.
+pub fn command_name(command: &Command) -> String {
+    let mut name = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        name.push(' ');
+        name.push_str(&arg.to_string_lossy());
+    }
+    name
+}
+
+fn set_nonblocking(fd: libc::c_int) {
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+}
+
+impl Window {
+    /// Prints new output of the command of the window.
+    fn update_process(&mut self) {
+        let (output, exit) = match self.process {
+            Some(ref mut p) if p.exit_status().is_none() => (p.read(), p.exit_status()),
+            _ => return,
+        };
+        if !output.is_empty() {
+            self.write(&output);
+        }
+        if let Some(status) = exit {
+            self.show_exit_status(status);
+        }
+    }
+}
+
+impl Tcwm {
+    /// Splits the focused window and runs `command` with its output printed
+    /// to the new window. The exit status is added to the header when the
+    /// command finishes.
+    pub fn run_command(&mut self, command: &mut Command) -> Result<Id, CursesError> {
+        let w = self.split();
+        let id = w.borrow().id;
+        match Process::spawn(command) {
+            Ok(p) => {
+                let mut w = w.borrow_mut();
+                w.process = Some(p);
+                w.set_header(&command_name(command));
+                Ok(id)
+            }
+            Err(e) => {
+                self.close_window(id)?;
+                Err(CursesError::Io(e))
+            }
+        }
+    }
+    /// Prints new output of commands started with `run_command`. `next_event`
+    /// does this before reading input.
+    pub fn update_commands(&mut self) {
+        for ws in self.workspaces.iter() {
+            for w in ws.root.all_windows() {
+                w.borrow_mut().update_process();
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! Output from other threads.
+//!
+//! Windows live in `Rc<RefCell<>>` and can only be touched on the thread that
+//! owns the manager. A `WindowSender` from `Tcwm::sender` can be cloned and
+//! moved to worker threads, which use it to queue output, header changes and
+//! closing of windows by id. The manager applies them in `next_event`, in the
+//! order they were sent.
+
+use std::sync::mpsc;
+
+use {Id, StyledText, Tcwm};
+
+pub enum Message {
+    Print(Id, StyledText),
+    Write(Id, String),
+    SetHeader(Id, String),
+    Close(Id),
+}
+
+/// Queues changes to windows from any thread.
+#[derive(Clone)]
+pub struct WindowSender {
+    sender: mpsc::Sender<Message>,
+}
+impl WindowSender {
+    /// Prints a line to the window `id`. Returns false if the manager has
+    /// been dropped, like the other methods.
+    pub fn print<T>(&self, id: Id, text: T) -> bool
+        where T: Into<StyledText>
+    {
+        self.send(Message::Print(id, text.into()))
+    }
+    /// Like `Window::write`.
+    pub fn write(&self, id: Id, s: &str) -> bool {
+        self.send(Message::Write(id, s.into()))
+    }
+    pub fn set_header(&self, id: Id, header: &str) -> bool {
+        self.send(Message::SetHeader(id, header.into()))
+    }
+    pub fn close(&self, id: Id) -> bool {
+        self.send(Message::Close(id))
+    }
+    fn send(&self, message: Message) -> bool {
+        self.sender.send(message).is_ok()
+    }
+}
+
+impl Tcwm {
+    /// A handle for changing windows from other threads.
+    pub fn sender(&self) -> WindowSender {
+        WindowSender { sender: self.sender.clone() }
+    }
+    /// Applies the changes queued with `WindowSender`s. Changes to windows
+    /// that have been closed are dropped. `next_event` does this before
+    /// reading input.
+    pub fn apply_messages(&mut self) {
+        while let Ok(message) = self.messages.try_recv() {
+            // The window may have been closed since the message was sent
+            let _ = match message {
+                Message::Print(id, text) => self.print_to(id, text),
+                Message::Write(id, s) => self.write_to(id, &s),
+                Message::SetHeader(id, header) => self.set_header_of(id, &header),
+                Message::Close(id) => {
+                    let mut result = Ok(());
+                    self.track_changes(|wm| result = wm.close_window(id));
+                    result
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Terminal panes.
+//!
+//! A terminal pane runs a program on a pseudo-terminal. Its output goes
+//! through a VT100 emulator, and the screen of the emulator becomes the buffer
+//! of the window. Keys that reach the focused pane are sent to the program,
+//! and the size of the pty follows the size of the window.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::ptr;
+
+use libc;
+use vt100;
+
+use process::command_name;
+use {CursesError, Id, Key, Style, StyledText, Tcwm, ThemeColor, Window};
+
+pub struct Terminal {
+    master: File,
+    child: Child,
+    parser: vt100::Parser,
+    exit: Option<ExitStatus>,
+}
+impl Terminal {
+    /// Starts `command` on a new pty of `cols` x `rows` cells.
+    pub fn spawn(command: &mut Command, cols: u16, rows: u16) -> io::Result<Terminal> {
+        let size = winsize(cols, rows);
+        let (mut master, mut slave) = (0, 0);
+        let ret = unsafe {
+            libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), &size)
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Other programs started later must not inherit the pty, or it never
+        // reports the end of output
+        unsafe {
+            libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+            let flags = libc::fcntl(master, libc::F_GETFL);
+            libc::fcntl(master, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+        let master = unsafe { File::from_raw_fd(master) };
+        let slave = unsafe { File::from_raw_fd(slave) };
+        command.stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave))
+            .env("TERM", "xterm-256color");
+        unsafe {
+            command.pre_exec(|| {
+                // A session of its own with the pty as the controlling terminal
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn();
+        // The command keeps its stdio until it is changed, so the copies of
+        // the slave are closed here
+        command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+        let child = child?;
+        Ok(Terminal {
+            master,
+            child,
+            parser: vt100::Parser::new(rows, cols, 0),
+            exit: None,
+        })
+    }
+    /// Reads the output that is available. Returns true if the screen may
+    /// have changed.
+    pub fn read(&mut self) -> bool {
+        if self.exit.is_some() {
+            return false;
+        }
+        let mut buf = [0; 4096];
+        let mut changed = false;
+        loop {
+            match self.master.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.parser.process(&buf[..n]);
+                    changed = true;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return changed,
+                // EIO once the program has closed the pty
+                Err(_) => break,
+            }
+        }
+        self.exit = self.child.try_wait().ok().and_then(|status| status);
+        changed || self.exit.is_some()
+    }
+    pub fn is_running(&self) -> bool {
+        self.exit.is_none()
+    }
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit
+    }
+    pub fn send_key(&mut self, key: Key) {
+        let bytes = key_bytes(key, self.parser.screen().application_cursor());
+        let _ = self.master.write_all(&bytes);
+    }
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        if self.parser.screen().size() == (rows, cols) {
+            return;
+        }
+        self.parser.set_size(rows, cols);
+        let size = winsize(cols, rows);
+        unsafe {
+            let fd = ::std::os::unix::io::AsRawFd::as_raw_fd(&self.master);
+            libc::ioctl(fd, libc::TIOCSWINSZ, &size);
+        }
+    }
+    /// The rows of the emulator screen, with the cursor in reverse video.
+    pub fn lines(&self) -> Vec<StyledText> {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let cursor = if screen.hide_cursor() || self.exit.is_some() {
+            None
+        } else {
+            Some(screen.cursor_position())
+        };
+        (0..rows)
+            .map(|row| {
+                let mut cells = vec![];
+                for col in 0..cols {
+                    let cell = match screen.cell(row, col) {
+                        Some(cell) => cell,
+                        None => continue,
+                    };
+                    if cell.is_wide_continuation() {
+                        continue;
+                    }
+                    let mut style = Style {
+                        fg: theme_color(cell.fgcolor()),
+                        bg: theme_color(cell.bgcolor()),
+                        bold: cell.bold(),
+                        italic: cell.italic(),
+                        underline: cell.underline(),
+                        reverse: cell.inverse(),
+                        ..Style::default()
+                    };
+                    if cursor == Some((row, col)) {
+                        style.reverse = !style.reverse;
+                    }
+                    let contents = if cell.has_contents() {
+                        cell.contents()
+                    } else {
+                        " ".to_owned()
+                    };
+                    cells.push((contents, style));
+                }
+                // Blank cells at the end of the row are left out
+                while cells.last().is_some_and(|c| c.0 == " " && c.1 == Style::default()) {
+                    cells.pop();
+                }
+                let mut text = StyledText::new();
+                for (contents, style) in cells {
+                    text.push(&contents, style);
+                }
+                text
+            })
+            .collect()
+    }
+}
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.exit.is_none() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+fn winsize(cols: u16, rows: u16) -> libc::winsize {
+    libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    }
+}
+
+fn theme_color(color: vt100::Color) -> Option<ThemeColor> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(i) => Some(ThemeColor::Indexed(i)),
+        vt100::Color::Rgb(r, g, b) => Some(ThemeColor::Rgb(r, g, b)),
+    }
+}
+
+/// The bytes an xterm sends for `key`.
+fn key_bytes(key: Key, application_cursor: bool) -> Vec<u8> {
+    let cursor = |c: char| if application_cursor {
+        format!("\x1bO{}", c)
+    } else {
+        format!("\x1b[{}", c)
+    };
+    let s = match key {
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => return vec![(c as u8) & 0x1f],
+        Key::Alt(c) => format!("\x1b{}", c),
+        Key::Enter => "\r".into(),
+        Key::Tab => "\t".into(),
+        Key::BackTab => "\x1b[Z".into(),
+        Key::Backspace => "\x7f".into(),
+        Key::Esc => "\x1b".into(),
+        Key::Up => cursor('A'),
+        Key::Down => cursor('B'),
+        Key::Right => cursor('C'),
+        Key::Left => cursor('D'),
+        Key::Home => cursor('H'),
+        Key::End => cursor('F'),
+        Key::PageUp => "\x1b[5~".into(),
+        Key::PageDown => "\x1b[6~".into(),
+        Key::Delete => "\x1b[3~".into(),
+        Key::F(n @ 1..=4) => format!("\x1bO{}", (b'P' + n - 1) as char),
+        Key::F(n) => {
+            let code = match n {
+                5 => 15,
+                6..=10 => n + 11,
+                11..=14 => n + 12,
+                _ => return vec![],
+            };
+            format!("\x1b[{}~", code)
+        }
+        Key::Other(_) => return vec![],
+    };
+    s.into_bytes()
+}
+
+impl Window {
+    /// Whether the window is a terminal pane.
+    pub fn is_terminal(&self) -> bool {
+        self.terminal.is_some()
+    }
+}
+
+impl Tcwm {
+    /// Splits the focused window and runs `command` in the new window on a
+    /// pty. Keys from `next_event` and `run` go to the program while the
+    /// window is focused, except the ones in the key bindings. `wait_for_key`
+    /// leaves keys and output of the pane alone.
+    pub fn open_terminal(&mut self, command: &mut Command) -> Result<Id, CursesError> {
+        let w = self.split();
+        let id = w.borrow().id;
+        let result = {
+            let mut w = w.borrow_mut();
+            let (cols, rows) = (w.content_width().max(1), w.content_height().max(1));
+            Terminal::spawn(command, cols as u16, rows as u16).map(|t| {
+                w.terminal = Some(t);
+                w.set_header(&command_name(command));
+                w.update_terminal();
+            })
+        };
+        match result {
+            Ok(()) => Ok(id),
+            Err(e) => {
+                self.close_window(id)?;
+                Err(CursesError::Io(e))
+            }
+        }
+    }
+    /// Shows new output of all terminal panes. `next_event` does this before
+    /// reading input.
+    pub fn update_terminals(&mut self) {
+        for ws in self.workspaces.iter() {
+            for w in ws.root.all_windows() {
+                w.borrow_mut().update_terminal();
+            }
+        }
+    }
+}
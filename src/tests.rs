@@ -0,0 +1,2081 @@
+use std::rc::Rc;
+
+use super::*;
+
+fn setup(width: i32, height: i32) -> (Rc<HeadlessBackend>, Tcwm) {
+    let backend = Rc::new(HeadlessBackend::new(width, height));
+    let wm = Tcwm::with_backend(backend.clone());
+    (backend, wm)
+}
+
+fn windows(c: &WindowContainer) -> Vec<WindowRef> {
+    let mut ret = vec![];
+    for pl in c.payload.iter() {
+        match *pl {
+            WindowPayload::Window(ref w) => ret.push(w.clone()),
+            WindowPayload::Container(ref c) => ret.extend(windows(&c.borrow())),
+        }
+    }
+    ret
+}
+
+fn geometry(c: &WindowContainer) -> Vec<(i32, i32, i32, i32)> {
+    windows(c)
+        .iter()
+        .map(|w| {
+            let w = w.borrow();
+            (w.x, w.y, w.xmax, w.ymax)
+        })
+        .collect()
+}
+
+#[test]
+fn single_window_fills_screen() {
+    let (backend, wm) = setup(40, 10);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    let w = wm.focused_window();
+    let w = w.borrow();
+    assert_eq!(backend.geometry(w.win), Some((0, 0, 40, 9)));
+    assert_eq!(backend.geometry(w.header_win), Some((0, 9, 40, 1)));
+    assert_eq!(backend.surface_count(), 2);
+}
+
+#[test]
+fn vertical_split_divides_width() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    wm.split();
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 13, 10), (13, 0, 13, 10), (26, 0, 14, 10)]);
+
+    let last = windows(&wm).pop().unwrap();
+    let last = last.borrow();
+    assert_eq!(backend.geometry(last.border_win.unwrap()), Some((26, 0, 1, 9)));
+    assert_eq!(backend.geometry(last.win), Some((27, 0, 13, 9)));
+    assert_eq!(backend.geometry(last.header_win), Some((26, 9, 14, 1)));
+}
+
+#[test]
+fn split_returns_focused_new_window() {
+    let (_backend, mut wm) = setup(40, 10);
+    let new = wm.split();
+    assert!(new == wm.focused_window());
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn set_split_direction_nests_container() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    assert!(wm.payload[0].is_window());
+    let c = wm.payload[1].as_container();
+    assert_eq!(c.borrow().direction, WindowSplitDirection::Horizontal);
+    assert_eq!(c.borrow().payload.len(), 2);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 0, 20, 5), (20, 5, 20, 5)]);
+}
+
+#[test]
+fn set_split_direction_on_single_window_changes_root() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    assert_eq!(wm.direction, WindowSplitDirection::Horizontal);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 5), (0, 5, 40, 5)]);
+}
+
+#[test]
+fn delete_only_window_does_nothing() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.delete();
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    assert_eq!(backend.surface_count(), 2);
+}
+
+#[test]
+fn delete_removes_window_and_surfaces() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.split();
+    assert_eq!(backend.surface_count(), 8);
+    wm.delete();
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    assert_eq!(wm.focus, 1);
+    assert_eq!(backend.surface_count(), 5);
+}
+
+#[test]
+fn delete_last_window_of_container_removes_container() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.delete();
+    assert_eq!(wm.payload.len(), 1);
+    assert!(wm.payload[0].is_window());
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+}
+
+#[test]
+fn on_resize_follows_screen_size() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    backend.resize_screen(60, 20);
+    assert_eq!(wm.wait_for_key(), RESIZE);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 30, 20), (30, 0, 30, 10), (30, 10, 30, 10)]);
+}
+
+#[test]
+fn change_focus_moves_between_windows() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.change_focus(Direction::Left);
+    assert_eq!(wm.focus, 0);
+    wm.change_focus(Direction::Left);
+    assert_eq!(wm.focus, 0);
+    wm.change_focus(Direction::Up);
+    assert_eq!(wm.focus, 0);
+    wm.change_focus(Direction::Right);
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn snapshot_split_windows() {
+    let (backend, mut wm) = setup(40, 6);
+    wm.print("left");
+    wm.split();
+    wm.print("right");
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.print("bottom");
+    wm.set_header("bottom");
+    assert_eq!(backend.screen(),
+               ["left                |right",
+                "                    |",
+                "                      Container 0 (20, 0",
+                "                    |bottom",
+                "                    |",
+                " Window 0 (0, 0) (20  bottom"]
+                   .join("\n"));
+}
+
+#[test]
+fn snapshot_after_resize() {
+    let (backend, mut wm) = setup(40, 6);
+    wm.print("left");
+    wm.split();
+    wm.print("right");
+    backend.resize_screen(30, 4);
+    wm.wait_for_key();
+    assert_eq!(backend.screen(),
+               ["left           |right",
+                "               |",
+                "               |",
+                " Window 0 (0, 0  Window 1 (15,"]
+                   .join("\n"));
+}
+
+#[test]
+fn managers_are_independent() {
+    let (first_backend, mut first) = setup(40, 10);
+    let (second_backend, mut second) = setup(20, 4);
+    first.split();
+    first.print("first");
+    second.print("second");
+    assert_eq!(geometry(&first), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    assert_eq!(geometry(&second), vec![(0, 0, 20, 4)]);
+    assert!(first_backend.screen().contains("first"));
+    assert!(!first_backend.screen().contains("second"));
+    assert!(second_backend.screen().contains("second"));
+}
+
+#[test]
+fn manager_can_be_recreated() {
+    let backend = Rc::new(HeadlessBackend::new(40, 10));
+    {
+        let mut wm = Tcwm::with_backend(backend.clone());
+        wm.split();
+        assert_eq!(backend.surface_count(), 5);
+    }
+    assert_eq!(backend.surface_count(), 0);
+    let wm = Tcwm::with_backend(backend.clone());
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    assert_eq!(backend.surface_count(), 2);
+}
+
+#[test]
+fn delete_removes_nested_empty_container() {
+    let (_backend, mut wm) = setup(40, 12);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Vertical);
+    wm.delete();
+    let c = wm.payload[1].as_container();
+    assert_eq!(c.borrow().payload.len(), 1);
+    assert!(c.borrow().payload[0].is_window());
+}
+
+#[test]
+fn grow_ignores_tiles_while_floating_window_is_focused() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    let before = geometry(&wm);
+    wm.new_floating(5, 2, 10, 4);
+    wm.grow(Dimension::Width, ResizeAmount::Cells(5));
+    wm.shrink(Dimension::Height, ResizeAmount::Cells(1));
+    assert_eq!(geometry(&wm), before);
+}
+
+#[test]
+fn grow_by_cells() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(10));
+    assert_eq!(geometry(&wm), vec![(0, 0, 10, 10), (10, 0, 30, 10)]);
+    wm.shrink(Dimension::Width, ResizeAmount::Cells(5));
+    assert_eq!(geometry(&wm), vec![(0, 0, 15, 10), (15, 0, 25, 10)]);
+}
+
+#[test]
+fn grow_by_percent() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.change_focus(Direction::Left);
+    wm.grow(Dimension::Width, ResizeAmount::Percent(25));
+    assert_eq!(geometry(&wm), vec![(0, 0, 30, 10), (30, 0, 10, 10)]);
+}
+
+#[test]
+fn grow_in_wrong_dimension_does_nothing() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.grow(Dimension::Height, ResizeAmount::Cells(3));
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+}
+
+#[test]
+fn shrink_stops_at_minimum_size() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.split();
+    wm.shrink(Dimension::Width, ResizeAmount::Cells(100));
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 19, 10), (19, 0, 19, 10), (38, 0, 2, 10)]);
+    wm.grow(Dimension::Width, ResizeAmount::Cells(100));
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 2, 10), (2, 0, 2, 10), (4, 0, 36, 10)]);
+}
+
+#[test]
+fn grow_uses_nearest_matching_container() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.grow(Dimension::Height, ResizeAmount::Cells(2));
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 0, 20, 3), (20, 3, 20, 7)]);
+    wm.grow(Dimension::Width, ResizeAmount::Cells(4));
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 16, 10), (16, 0, 24, 3), (16, 3, 24, 7)]);
+}
+
+#[test]
+fn weights_survive_resize() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(10));
+    backend.resize_screen(80, 10);
+    wm.wait_for_key();
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 60, 10)]);
+}
+
+#[test]
+fn split_after_grow_gets_average_slot() {
+    let (_backend, mut wm) = setup(60, 10);
+    wm.split();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(10));
+    wm.split();
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 13, 10), (13, 0, 27, 10), (40, 0, 20, 10)]);
+}
+
+// Builds V[W, H[W, W], W]
+fn mixed_layout(wm: &mut Tcwm) {
+    wm.split();
+    wm.split();
+    wm.change_focus(Direction::Left);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+}
+
+#[test]
+fn split_inserts_after_focused_window() {
+    let (_backend, mut wm) = setup(60, 10);
+    let second = wm.split();
+    wm.change_focus(Direction::Left);
+    let middle = wm.split();
+    let ws = windows(&wm);
+    assert!(ws[1] == middle);
+    assert!(ws[2] == second);
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn layout_mixed_windows_and_containers() {
+    let (_backend, mut wm) = setup(60, 10);
+    mixed_layout(&mut wm);
+    assert!(wm.payload[0].is_window());
+    assert!(wm.payload[1].is_container());
+    assert!(wm.payload[2].is_window());
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 0, 20, 5), (20, 5, 20, 5), (40, 0, 20, 10)]);
+}
+
+#[test]
+fn layout_mixed_horizontal_root() {
+    let (_backend, mut wm) = setup(40, 12);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.split();
+    wm.change_focus(Direction::Up);
+    wm.set_split_direction(WindowSplitDirection::Vertical);
+    wm.split();
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 40, 4), (0, 4, 20, 4), (20, 4, 20, 4), (0, 8, 40, 4)]);
+}
+
+#[test]
+fn layout_deeply_nested() {
+    let (_backend, mut wm) = setup(40, 12);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Vertical);
+    wm.split();
+    // V[W, H[W, V[W, W]]]
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 12), (20, 0, 20, 6), (20, 6, 10, 6), (30, 6, 10, 6)]);
+}
+
+#[test]
+fn layout_mixed_after_resize() {
+    let (backend, mut wm) = setup(60, 10);
+    mixed_layout(&mut wm);
+    backend.resize_screen(31, 7);
+    wm.wait_for_key();
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 10, 7), (10, 0, 10, 3), (10, 3, 10, 4), (20, 0, 11, 7)]);
+}
+
+#[test]
+fn layout_mixed_after_delete() {
+    let (backend, mut wm) = setup(60, 10);
+    mixed_layout(&mut wm);
+    wm.change_focus(Direction::Left);
+    wm.delete();
+    // H[W, W], W
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 30, 5), (0, 5, 30, 5), (30, 0, 30, 10)]);
+    // The windows that moved to the left edge lost their borders
+    for w in windows(&wm).iter().take(2) {
+        let w = w.borrow();
+        assert!(w.border_win.is_none());
+        assert_eq!(backend.geometry(w.win), Some((0, w.y, 30, 4)));
+    }
+}
+
+#[test]
+fn snapshot_mixed_layout() {
+    let (backend, mut wm) = setup(45, 6);
+    mixed_layout(&mut wm);
+    wm.print("top");
+    wm.set_header("H[1]");
+    assert_eq!(backend.screen(),
+               ["               |              |",
+                "               |              |",
+                "                 Container 0 (|",
+                "               |top           |",
+                "               |              |",
+                " Window 0 (0, 0  H[1]           Window 2 (30,"]
+                   .join("\n"));
+}
+
+fn contents(backend: &HeadlessBackend, wm: &WindowContainer) -> Vec<String> {
+    let w = wm.focused_window();
+    let w = w.borrow();
+    backend.contents(w.win)
+        .unwrap()
+        .into_iter()
+        .map(|row| row.trim_end().to_owned())
+        .collect()
+}
+
+/// The buffer of a window without styles.
+fn text(w: &Window) -> Vec<String> {
+    w.lines.iter().map(|line| line.text()).collect()
+}
+
+fn print_numbers(wm: &mut WindowContainer, range: ::std::ops::Range<usize>) {
+    for i in range {
+        wm.print(format!("line {}", i));
+    }
+}
+
+#[test]
+fn print_scrolls_to_newest_line() {
+    let (backend, mut wm) = setup(20, 5);
+    print_numbers(&mut wm, 0..6);
+    assert_eq!(contents(&backend, &wm), ["line 2", "line 3", "line 4", "line 5"]);
+}
+
+#[test]
+fn long_lines_wrap_and_scroll() {
+    let (backend, mut wm) = setup(10, 4);
+    wm.print("short");
+    wm.print("0123456789abcdef");
+    wm.print("end");
+    assert_eq!(contents(&backend, &wm), ["0123456789", "abcdef", "end"]);
+}
+
+#[test]
+fn scroll_up_stops_following() {
+    let (backend, mut wm) = setup(20, 5);
+    print_numbers(&mut wm, 0..10);
+    wm.scroll_up(3);
+    assert_eq!(contents(&backend, &wm), ["line 3", "line 4", "line 5", "line 6"]);
+    print_numbers(&mut wm, 10..12);
+    assert_eq!(contents(&backend, &wm), ["line 3", "line 4", "line 5", "line 6"]);
+    assert!(!wm.focused_window().borrow().is_following());
+    wm.scroll_down(1);
+    assert_eq!(contents(&backend, &wm), ["line 4", "line 5", "line 6", "line 7"]);
+    wm.scroll_to_bottom();
+    assert!(wm.focused_window().borrow().is_following());
+    assert_eq!(contents(&backend, &wm), ["line 8", "line 9", "line 10", "line 11"]);
+}
+
+#[test]
+fn scroll_is_limited_to_buffer() {
+    let (backend, mut wm) = setup(20, 5);
+    print_numbers(&mut wm, 0..6);
+    wm.scroll_up(100);
+    assert_eq!(contents(&backend, &wm), ["line 0", "line 1", "line 2", "line 3"]);
+    wm.scroll_down(100);
+    assert!(wm.focused_window().borrow().is_following());
+}
+
+#[test]
+fn page_up_and_down() {
+    let (backend, mut wm) = setup(20, 5);
+    print_numbers(&mut wm, 0..12);
+    wm.page_up();
+    assert_eq!(contents(&backend, &wm), ["line 4", "line 5", "line 6", "line 7"]);
+    wm.page_up();
+    assert_eq!(contents(&backend, &wm), ["line 0", "line 1", "line 2", "line 3"]);
+    wm.page_down();
+    assert_eq!(contents(&backend, &wm), ["line 4", "line 5", "line 6", "line 7"]);
+}
+
+#[test]
+fn scrollback_is_bounded() {
+    let (backend, mut wm) = setup(20, 5);
+    wm.focused_window().borrow_mut().set_scrollback(5);
+    print_numbers(&mut wm, 0..20);
+    assert_eq!(wm.focused_window().borrow().lines.len(), 5);
+    wm.scroll_up(100);
+    assert_eq!(contents(&backend, &wm), ["line 15", "line 16", "line 17", "line 18"]);
+}
+
+#[test]
+fn print_overwriting_replaces_previous_overwriting_line() {
+    let (backend, mut wm) = setup(20, 5);
+    wm.print("start");
+    wm.print_overwriting("10%");
+    wm.print_overwriting("20%");
+    assert_eq!(contents(&backend, &wm), ["start", "20%", "", ""]);
+    wm.print("done");
+    wm.print_overwriting("0%");
+    assert_eq!(contents(&backend, &wm), ["start", "20%", "done", "0%"]);
+}
+
+#[test]
+fn scroll_position_survives_resize() {
+    let (backend, mut wm) = setup(20, 5);
+    print_numbers(&mut wm, 0..10);
+    wm.scroll_up(2);
+    backend.resize_screen(20, 4);
+    wm.wait_for_key();
+    assert_eq!(contents(&backend, &wm), ["line 5", "line 6", "line 7"]);
+}
+
+#[test]
+fn tabbed_children_share_container_area() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    wm.split();
+    wm.split();
+    assert_eq!(geometry(&wm),
+               vec![(0, 1, 40, 9), (0, 1, 40, 9), (0, 1, 40, 9)]);
+    let tabs = wm.titles.iter().map(|t| backend.geometry(*t).unwrap()).collect::<Vec<_>>();
+    assert_eq!(tabs, vec![(0, 0, 13, 1), (13, 0, 13, 1), (26, 0, 14, 1)]);
+    assert_eq!(backend.color(wm.titles[2]), Some(Color::StatusSelected));
+    assert_eq!(backend.color(wm.titles[0]), Some(Color::Status));
+}
+
+#[test]
+fn tabbed_shows_only_focused_child() {
+    let (backend, mut wm) = setup(40, 5);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    wm.print("first");
+    wm.split();
+    wm.print("second");
+    assert_eq!(backend.screen(),
+               [" Window 0 (0, 0) (40 Window 1 (0, 1) (40",
+                "second",
+                "",
+                "",
+                " Window 1 (0, 1) (40, 4)"]
+                   .join("\n"));
+    wm.change_focus(Direction::Left);
+    assert_eq!(wm.focus, 0);
+    assert_eq!(backend.screen(),
+               [" Window 0 (0, 1) (40 Window 1 (0, 1) (40",
+                "first",
+                "",
+                "",
+                " Window 0 (0, 1) (40, 4)"]
+                   .join("\n"));
+    assert_eq!(backend.color(wm.titles[0]), Some(Color::StatusSelected));
+}
+
+#[test]
+fn hidden_tab_output_is_not_drawn() {
+    let (backend, mut wm) = setup(40, 5);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    let first = wm.focused_window();
+    wm.split();
+    first.borrow_mut().print("in the background");
+    assert!(!backend.screen().contains("background"));
+    wm.change_focus(Direction::Left);
+    assert!(backend.screen().contains("background"));
+}
+
+#[test]
+fn stacked_children_are_below_title_rows() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.set_split_direction(WindowSplitDirection::Stacked);
+    wm.split();
+    wm.split();
+    assert_eq!(geometry(&wm),
+               vec![(0, 3, 40, 7), (0, 3, 40, 7), (0, 3, 40, 7)]);
+    let rows = wm.titles.iter().map(|t| backend.geometry(*t).unwrap()).collect::<Vec<_>>();
+    assert_eq!(rows, vec![(0, 0, 40, 1), (0, 1, 40, 1), (0, 2, 40, 1)]);
+    wm.change_focus(Direction::Left);
+    assert_eq!(wm.focus, 2);
+    wm.change_focus(Direction::Up);
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn title_rows_and_tabs_fit_in_small_container() {
+    let (backend, mut wm) = setup(6, 5);
+    wm.set_split_direction(WindowSplitDirection::Stacked);
+    for _ in 0..8 {
+        wm.split();
+    }
+    assert_eq!(wm.titles.len(), 3);
+    for (x, y, width, height) in geometry(&wm) {
+        assert!(x >= 0 && y >= 0 && width > 0 && height > 0);
+        assert!(x + width <= 6 && y + height <= 5);
+    }
+    wm.set_layout(WindowSplitDirection::Tabbed);
+    let tabs = wm.titles.iter().map(|t| backend.geometry(*t).unwrap()).collect::<Vec<_>>();
+    assert_eq!(tabs.len(), 6);
+    assert!(tabs.iter().all(|&(_, _, width, _)| width == 1));
+    assert_eq!(geometry(&wm)[0], (0, 1, 6, 4));
+}
+
+#[test]
+fn set_layout_changes_focused_container() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.set_layout(WindowSplitDirection::Tabbed);
+    assert_eq!(wm.direction, WindowSplitDirection::Vertical);
+    let c = wm.payload[1].as_container();
+    assert_eq!(c.borrow().direction, WindowSplitDirection::Tabbed);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 1, 20, 9), (20, 1, 20, 9)]);
+    wm.set_layout(WindowSplitDirection::Horizontal);
+    assert!(c.borrow().titles.is_empty());
+    assert_eq!(backend.surface_count(), 8);
+}
+
+#[test]
+fn focus_leaves_tabbed_container_at_last_tab() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.change_focus(Direction::Left);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    wm.split();
+    let c = wm.payload[0].as_container();
+    assert_eq!(c.borrow().focus, 1);
+    wm.change_focus(Direction::Right);
+    assert_eq!(wm.focus, 1);
+    wm.change_focus(Direction::Left);
+    assert_eq!(wm.focus, 0);
+    assert_eq!(c.borrow().focus, 1);
+    wm.change_focus(Direction::Left);
+    assert_eq!(c.borrow().focus, 0);
+}
+
+#[test]
+fn floating_window_is_drawn_above_tiled() {
+    let (backend, mut wm) = setup(30, 8);
+    let tiled = wm.focused_window();
+    for i in 0..7 {
+        wm.print(format!("tiled line {}", i));
+    }
+    let popup = wm.new_floating(5, 2, 15, 4);
+    wm.print("popup");
+    assert!(text(&popup.borrow()).contains(&"popup".to_owned()));
+    tiled.borrow_mut().print("more tiled output");
+    assert_eq!(backend.screen(),
+               ["tiled line 1",
+                "tiled line 2",
+                "tiled|popup",
+                "tiled|",
+                "tiled|",
+                "tiled  New window",
+                "more tiled output",
+                " Window 0 (0, 0) (30, 8)"]
+                   .join("\n"));
+}
+
+#[test]
+fn raise_changes_stacking_order() {
+    let (backend, mut wm) = setup(30, 8);
+    let first = wm.new_floating(2, 1, 10, 4);
+    wm.print("first");
+    wm.new_floating(2, 1, 10, 4);
+    wm.print("second");
+    assert!(backend.screen().contains("second"));
+    assert!(!backend.screen().contains("first"));
+    wm.raise(&first);
+    assert!(backend.screen().contains("first"));
+    assert!(!backend.screen().contains("second"));
+    assert!(wm.focused() == first);
+}
+
+#[test]
+fn move_and_resize_floating() {
+    let (backend, mut wm) = setup(30, 8);
+    let popup = wm.new_floating(2, 1, 10, 4);
+    wm.move_floating(&popup, 10, 3);
+    wm.resize_floating(&popup, 12, 5);
+    {
+        let w = popup.borrow();
+        assert_eq!((w.x, w.y, w.xmax, w.ymax), (10, 3, 12, 5));
+        assert_eq!(backend.geometry(w.header_win), Some((10, 7, 12, 1)));
+    }
+    let rows = backend.screen().lines().map(|l| l.to_owned()).collect::<Vec<_>>();
+    assert_eq!(rows[1], "");
+    assert_eq!(rows[7], " Window 0   New window8)");
+}
+
+#[test]
+fn toggle_floating_keeps_buffer() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.print("keep me");
+    let win = wm.focused_window();
+    wm.toggle_floating();
+    assert!(wm.focused() == win);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    {
+        let w = win.borrow();
+        assert_eq!((w.x, w.y, w.xmax, w.ymax), (10, 2, 20, 5));
+    }
+    wm.toggle_floating();
+    assert!(wm.floating.is_empty());
+    assert!(wm.focused() == win);
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    assert_eq!(text(&win.borrow()), vec!["keep me".to_owned()]);
+}
+
+#[test]
+fn only_window_cannot_float() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.toggle_floating();
+    assert!(wm.floating.is_empty());
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+}
+
+#[test]
+fn delete_closes_focused_floating_window() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.new_floating(5, 2, 10, 4);
+    assert_eq!(backend.surface_count(), 5);
+    wm.delete();
+    assert!(wm.floating.is_empty());
+    assert!(!wm.floating_focused);
+    assert_eq!(backend.surface_count(), 2);
+}
+
+#[test]
+fn toggle_focus_mode_moves_focus_to_tiled() {
+    let (_backend, mut wm) = setup(40, 10);
+    let tiled = wm.focused_window();
+    let popup = wm.new_floating(5, 2, 10, 4);
+    assert!(wm.focused() == popup);
+    assert_eq!(popup.borrow().header_color, Color::StatusSelected);
+    wm.toggle_focus_mode();
+    assert!(wm.focused() == tiled);
+    assert_eq!(popup.borrow().header_color, Color::Status);
+    assert_eq!(tiled.borrow().header_color, Color::StatusSelected);
+    wm.toggle_focus_mode();
+    assert!(wm.focused() == popup);
+}
+
+#[test]
+fn switch_workspace_creates_fresh_window() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.print("first");
+    let first = wm.focused_window();
+    wm.switch_workspace("2");
+    assert_eq!(wm.current_workspace(), "2");
+    assert!(wm.focused_window() != first);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    assert!(!backend.screen().contains("first"));
+    assert_eq!(backend.surface_count(), 4);
+}
+
+#[test]
+fn hidden_workspace_buffers_output() {
+    let (backend, mut wm) = setup(40, 10);
+    let first = wm.focused_window();
+    wm.switch_workspace("2");
+    first.borrow_mut().print("while hidden");
+    assert!(!backend.screen().contains("while hidden"));
+    wm.print("second");
+    wm.switch_workspace("1");
+    assert!(backend.screen().contains("while hidden"));
+    assert!(!backend.screen().contains("second"));
+}
+
+#[test]
+fn move_to_workspace_transfers_window() {
+    let (backend, mut wm) = setup(40, 10);
+    let moved = wm.split();
+    wm.print("moved");
+    wm.move_to_workspace("logs");
+    assert_eq!(wm.current_workspace(), "1");
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+    assert!(!backend.screen().contains("moved"));
+
+    let list = wm.workspaces();
+    assert_eq!(list.len(), 2);
+    assert_eq!(list[0].1.len(), 1);
+    assert_eq!(list[1].0, "logs");
+    assert_eq!(list[1].1.len(), 2);
+    assert!(list[1].1[1] == moved);
+
+    wm.switch_workspace("logs");
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    assert!(backend.screen().contains("moved"));
+}
+
+#[test]
+fn last_window_stays_in_workspace() {
+    let (_backend, mut wm) = setup(40, 10);
+    let only = wm.focused_window();
+    wm.move_to_workspace("2");
+    assert!(wm.focused_window() == only);
+    assert_eq!(wm.workspaces()[1].1.len(), 1);
+}
+
+#[test]
+fn floating_windows_belong_to_their_workspace() {
+    let (backend, mut wm) = setup(40, 10);
+    let popup = wm.new_floating(5, 2, 10, 4);
+    popup.borrow_mut().print("popup");
+    assert!(backend.screen().contains("popup"));
+    wm.switch_workspace("2");
+    assert!(!backend.screen().contains("popup"));
+    assert!(wm.floating.is_empty());
+    wm.switch_workspace("1");
+    assert!(backend.screen().contains("popup"));
+}
+
+#[test]
+fn layout_round_trips_through_json() {
+    let (_backend, mut wm) = setup(45, 6);
+    mixed_layout(&mut wm);
+    wm.grow(Dimension::Height, ResizeAmount::Cells(1));
+    wm.set_header("logs");
+    wm.print("kept");
+    let saved = wm.save_layout(true);
+    let parsed = Layout::from_json(&saved.to_json()).unwrap();
+    assert!(parsed == saved);
+
+    let (_backend, mut other) = setup(45, 6);
+    other.restore_layout(&parsed).unwrap();
+    assert_eq!(geometry(&other), geometry(&wm));
+    assert!(other.save_layout(true) == saved);
+    let focused = other.focused();
+    let focused = focused.borrow();
+    assert_eq!(focused.header, "logs");
+    assert_eq!(text(&focused), vec!["kept".to_owned()]);
+}
+
+#[test]
+fn layout_without_buffers_drops_lines() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.print("gone");
+    let saved = wm.save_layout(false);
+    assert!(!saved.to_json().contains("gone"));
+    wm.restore_layout(&saved).unwrap();
+    assert!(wm.focused_window().borrow().lines.is_empty());
+}
+
+#[test]
+fn restore_preset_layout() {
+    let (backend, mut wm) = setup(40, 10);
+    let preset = r#"{
+        "root": {
+            "direction": "Vertical",
+            "focus": 1,
+            "children": [
+                {"Window": {"weight": 3.0, "header": "editor"}},
+                {"Container": {
+                    "direction": "Horizontal",
+                    "children": [{"Window": {}}, {"Window": {"header": "shell"}}]
+                }}
+            ]
+        },
+        "floating": [{"x": 5, "y": 2, "width": 10, "height": 4, "window": {}}]
+    }"#;
+    wm.restore_layout(&Layout::from_json(preset).unwrap()).unwrap();
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 30, 10), (30, 0, 10, 5), (30, 5, 10, 5)]);
+    assert_eq!(wm.floating.len(), 1);
+    assert!(!wm.floating_focused);
+    assert_eq!(wm.focused().borrow().header, "Container 0 (30, 0) (10, 5)");
+    assert!(backend.screen().contains(" editor"));
+    // Old windows are gone, three tiled and one floating are left
+    assert_eq!(backend.surface_count(), 11);
+}
+
+#[test]
+fn invalid_layout_is_rejected() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    let empty = r#"{"root": {"direction": "Vertical", "children": []}}"#;
+    match wm.restore_layout(&Layout::from_json(empty).unwrap()) {
+        Err(CursesError::InvalidLayout(_)) => {}
+        _ => panic!("empty container accepted"),
+    }
+    match Layout::from_json("{") {
+        Err(CursesError::InvalidLayout(_)) => {}
+        _ => panic!("broken json accepted"),
+    }
+    assert_eq!(windows(&wm).len(), 2);
+}
+
+#[test]
+fn find_searches_later_siblings() {
+    let (_backend, mut wm) = setup(60, 10);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.change_focus(Direction::Left);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.change_focus(Direction::Right);
+    let last = wm.split();
+    let id = last.borrow().id();
+    assert!(wm.payload[0].is_container());
+    assert!(wm.find(id).is_some());
+    assert!(wm.window(id).unwrap() == last);
+}
+
+#[test]
+fn print_and_retitle_by_id() {
+    let (backend, mut wm) = setup(40, 6);
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.print_to(first, "to the left").unwrap();
+    wm.set_header_of(first, "left").unwrap();
+    wm.print("right");
+    assert_eq!(backend.screen(),
+               ["to the left         |right",
+                "                    |",
+                "                    |",
+                "                    |",
+                "                    |",
+                " left                 Window 1 (20, 0) ("]
+                   .join("\n"));
+    // Headers set by id survive a refresh
+    wm.resize();
+    assert!(backend.screen().contains(" left "));
+}
+
+#[test]
+fn focus_window_by_id() {
+    let (_backend, mut wm) = setup(40, 10);
+    mixed_layout(&mut wm);
+    let ws = windows(&wm);
+    let id = ws[2].borrow().id();
+    wm.focus_window(id).unwrap();
+    assert!(wm.focused() == ws[2]);
+    let popup = wm.new_floating(5, 2, 10, 4);
+    let id = ws[0].borrow().id();
+    wm.focus_window(id).unwrap();
+    assert!(wm.focused() == ws[0]);
+    let id = popup.borrow().id();
+    wm.focus_window(id).unwrap();
+    assert!(wm.focused() == popup);
+}
+
+#[test]
+fn focus_window_switches_workspace() {
+    let (_backend, mut wm) = setup(40, 10);
+    let first = wm.focused_window();
+    let id = first.borrow().id();
+    wm.switch_workspace("2");
+    wm.focus_window(id).unwrap();
+    assert_eq!(wm.current_workspace(), "1");
+    assert!(wm.focused() == first);
+}
+
+#[test]
+fn close_window_keeps_focus() {
+    let (backend, mut wm) = setup(60, 10);
+    let first = wm.focused_window().borrow().id();
+    let second = wm.split().borrow().id();
+    let third = wm.split().borrow().id();
+    wm.close_window(second).unwrap();
+    assert_eq!(wm.focused().borrow().id(), third);
+    assert_eq!(geometry(&wm), vec![(0, 0, 30, 10), (30, 0, 30, 10)]);
+    wm.close_window(third).unwrap();
+    assert_eq!(wm.focused().borrow().id(), first);
+    assert_eq!(backend.surface_count(), 2);
+    // The last window stays
+    match wm.close_window(first) {
+        Err(CursesError::LastWindow(id)) => assert_eq!(id, first),
+        _ => panic!("last window closed"),
+    }
+    assert_eq!(geometry(&wm), vec![(0, 0, 60, 10)]);
+}
+
+#[test]
+fn closing_nested_window_removes_empty_containers() {
+    let (_backend, mut wm) = setup(60, 20);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    let middle = wm.split().borrow().id();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    let last = wm.split().borrow().id();
+    wm.close_window(middle).unwrap();
+    assert_eq!(wm.focused().borrow().id(), last);
+    wm.delete();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(5));
+    wm.set_layout(WindowSplitDirection::Vertical);
+    assert_eq!(windows(&wm).len(), 2);
+    wm.delete();
+    assert_eq!(geometry(&wm), vec![(0, 0, 60, 20)]);
+}
+
+#[test]
+fn closed_window_is_an_error() {
+    let (_backend, mut wm) = setup(40, 10);
+    let id = wm.split().borrow().id();
+    wm.close_window(id).unwrap();
+    match wm.print_to(id, "lost") {
+        Err(err @ CursesError::WindowNotFound(_)) => {
+            assert_eq!(err.to_string(), format!("window {} not found", id));
+        }
+        _ => panic!("closed window found"),
+    }
+    assert!(wm.window(id).is_err());
+    assert!(wm.focus_window(id).is_err());
+    assert!(wm.close_window(id).is_err());
+}
+
+#[test]
+fn print_to_draws_only_target_window() {
+    let (backend, mut wm) = setup(60, 6);
+    let first = wm.focused_window();
+    wm.split();
+    let third = wm.split();
+    let id = first.borrow().id();
+    backend.take_refreshed();
+    wm.print_to(id, "background").unwrap();
+    assert_eq!(backend.take_refreshed(), vec![first.borrow().win]);
+    assert!(wm.focused() == third);
+    assert_eq!(first.borrow().header_color, Color::Status);
+    // Filling the window redraws it, still without touching the others
+    for i in 0..10 {
+        wm.print_to(id, i.to_string()).unwrap();
+    }
+    let refreshed = backend.take_refreshed();
+    assert!(refreshed.iter().all(|s| *s == first.borrow().win));
+    assert!(backend.screen().starts_with("5"));
+}
+
+#[test]
+fn print_overwriting_to_replaces_line() {
+    let (backend, mut wm) = setup(40, 6);
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.print_overwriting_to(first, "10%").unwrap();
+    wm.print_overwriting_to(first, "20%").unwrap();
+    assert_eq!(text(&wm.window(first).unwrap().borrow()), vec!["20%".to_owned()]);
+    assert!(backend.screen().starts_with("20%"));
+}
+
+#[test]
+fn print_to_background_tab_is_buffered() {
+    let (backend, mut wm) = setup(40, 6);
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.set_layout(WindowSplitDirection::Tabbed);
+    wm.print_to(first, "hidden tab").unwrap();
+    assert!(!backend.screen().contains("hidden tab"));
+    wm.change_focus(Direction::Left);
+    assert!(backend.screen().contains("hidden tab"));
+}
+
+fn push_keys(backend: &HeadlessBackend, keys: &[i32]) {
+    for key in keys {
+        backend.push_key(*key);
+    }
+}
+
+#[test]
+fn keys_are_decoded() {
+    let (backend, mut wm) = setup(40, 10);
+    assert_eq!(wm.next_event(), None);
+    push_keys(&backend,
+              &[b'x' as i32, 3, 27, b'h' as i32, 10, ncurses::KEY_UP, 0xc3, 0xa4]);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Char('x'))));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Ctrl('c'))));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Alt('h'))));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Enter)));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Up)));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Char('ä'))));
+    backend.push_key(27);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Esc)));
+    // A key after Esc that Alt does not go with is kept
+    push_keys(&backend, &[27, ncurses::KEY_UP, 27, 27, b'q' as i32]);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Esc)));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Up)));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Esc)));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Alt('q'))));
+    assert_eq!(wm.next_event(), None);
+}
+
+#[test]
+fn resize_and_mouse_events() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    backend.resize_screen(60, 20);
+    assert_eq!(wm.next_event(), Some(Event::Resize(60, 20)));
+    assert_eq!(geometry(&wm), vec![(0, 0, 30, 20), (30, 0, 30, 20)]);
+    // A click in the focused window
+    let click = MouseEvent {
+        x: 43,
+        y: 4,
+        kind: MouseKind::Press,
+    };
+    backend.push_mouse(click);
+    assert_eq!(wm.next_event(), Some(Event::Mouse(click)));
+}
+
+#[test]
+fn bound_actions_report_focus_and_close() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.set_bindings(KeyBindings::defaults());
+    let first = wm.focused().borrow().id();
+    push_keys(&backend, &[27, 10]);
+    let second = match wm.next_event() {
+        Some(Event::FocusChanged(id)) => id,
+        e => panic!("unexpected {:?}", e),
+    };
+    assert!(second != first);
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+    push_keys(&backend, &[27, b'q' as i32]);
+    assert_eq!(wm.next_event(), Some(Event::WindowClosed(second)));
+    assert_eq!(wm.next_event(), Some(Event::FocusChanged(first)));
+    assert_eq!(wm.next_event(), None);
+    // Actions that change nothing produce no events
+    push_keys(&backend, &[27, b'h' as i32]);
+    assert_eq!(wm.next_event(), None);
+}
+
+#[test]
+fn callbacks_and_quit() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.bindings().bind_fn(Key::Ctrl('p'), |wm| wm.print("from callback"));
+    wm.bindings().bind(Key::Char('q'), Action::Quit);
+    push_keys(&backend, &[16, b'a' as i32, b'q' as i32, b'b' as i32]);
+    let mut seen = vec![];
+    wm.run(|_, event| seen.push(event));
+    assert_eq!(seen, vec![Event::Key(Key::Char('a'))]);
+    assert!(backend.screen().contains("from callback"));
+    wm.bindings().unbind(Key::Char('q'));
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Char('b'))));
+}
+
+#[test]
+fn line_editor_collects_input() {
+    let (backend, mut wm) = setup(40, 6);
+    let id = wm.focused().borrow().id();
+    wm.set_input_handler(id, LineEditor::new("> ")).unwrap();
+    push_keys(&backend, &[b'l' as i32, b's' as i32, b'x' as i32, 127, 10]);
+    assert_eq!(wm.next_event(), Some(Event::Input(id, "ls".into())));
+    assert_eq!(wm.next_event(), None);
+    push_keys(&backend, &[b'p' as i32]);
+    assert_eq!(wm.next_event(), None);
+    assert_eq!(contents(&backend, &wm)[..2], ["> ls".to_owned(), "> p".to_owned()]);
+    // Keys the editor does not use are passed on
+    backend.push_key(ncurses::KEY_UP);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Up)));
+}
+
+#[test]
+fn list_selector_picks_item() {
+    let (backend, mut wm) = setup(40, 6);
+    let id = wm.focused().borrow().id();
+    let items = vec!["one".to_owned(), "two".to_owned(), "three".to_owned()];
+    wm.set_input_handler(id, ListSelector::new(items)).unwrap();
+    assert!(backend.screen().starts_with("> one\n  two"));
+    push_keys(&backend,
+              &[ncurses::KEY_DOWN, ncurses::KEY_DOWN, ncurses::KEY_DOWN, ncurses::KEY_UP, 10]);
+    assert_eq!(wm.next_event(), Some(Event::Input(id, "two".into())));
+    assert!(backend.screen().starts_with("  one\n> two"));
+}
+
+#[test]
+fn list_selector_scrolls_to_selection() {
+    let (backend, mut wm) = setup(40, 4);
+    let id = wm.focused().borrow().id();
+    let items = (0..10).map(|i| i.to_string()).collect();
+    wm.set_input_handler(id, ListSelector::new(items)).unwrap();
+    assert!(backend.screen().starts_with("> 0\n  1\n  2"));
+    push_keys(&backend, &[ncurses::KEY_DOWN; 5]);
+    assert_eq!(wm.next_event(), None);
+    assert!(backend.screen().starts_with("  3\n  4\n> 5"));
+}
+
+#[test]
+fn handlers_follow_focus_and_bindings_come_first() {
+    let (backend, mut wm) = setup(40, 6);
+    let first = wm.focused().borrow().id();
+    wm.set_input_handler(first, |w: &mut Window, key| match key {
+            Key::Char(c) => {
+                w.print(c.to_string());
+                Handled::Consumed
+            }
+            _ => Handled::Ignored,
+        })
+        .unwrap();
+    wm.bindings().bind(Key::Char('n'), Action::Split);
+    push_keys(&backend, &[b'a' as i32, b'n' as i32]);
+    let second = match wm.next_event() {
+        Some(Event::FocusChanged(id)) => id,
+        e => panic!("unexpected {:?}", e),
+    };
+    assert_eq!(text(&wm.window(first).unwrap().borrow()), vec!["a".to_owned()]);
+    // The new window has no handler
+    backend.push_key(b'b' as i32);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Char('b'))));
+    wm.focus_window(first).unwrap();
+    backend.push_key(b'c' as i32);
+    assert_eq!(wm.next_event(), None);
+    assert_eq!(wm.window(first).unwrap().borrow().lines.len(), 2);
+    assert!(wm.window(second).unwrap().borrow().lines.is_empty());
+}
+
+fn press(backend: &HeadlessBackend, kind: MouseKind, x: i32, y: i32) {
+    backend.push_mouse(MouseEvent { x, y, kind });
+}
+
+fn drain(wm: &mut Tcwm) {
+    while wm.next_event().is_some() {}
+}
+
+#[test]
+fn mouse_is_opt_in() {
+    let (backend, mut wm) = setup(40, 10);
+    assert!(!backend.mouse_enabled());
+    wm.set_mouse(true);
+    assert!(backend.mouse_enabled());
+    wm.set_mouse(false);
+    assert!(!backend.mouse_enabled());
+}
+
+#[test]
+fn floating_window_surfaces_stay_inside_it() {
+    let (backend, mut wm) = setup(40, 10);
+    let popup = wm.new_floating(5, 2, 10, 4);
+    let popup = popup.borrow();
+    assert_eq!(backend.geometry(popup.win), Some((6, 2, 9, 3)));
+    assert_eq!(backend.geometry(popup.border_win.unwrap()), Some((5, 2, 1, 3)));
+    assert_eq!(backend.geometry(popup.header_win), Some((5, 5, 10, 1)));
+}
+
+#[test]
+fn click_focuses_window() {
+    let (backend, mut wm) = setup(60, 10);
+    mixed_layout(&mut wm);
+    let ws = windows(&wm);
+    press(&backend, MouseKind::Press, 5, 3);
+    assert_eq!(wm.next_event(),
+               Some(Event::FocusChanged(ws[0].borrow().id())));
+    assert_eq!(wm.next_event(),
+               Some(Event::Mouse(MouseEvent {
+                   x: 5,
+                   y: 3,
+                   kind: MouseKind::Press,
+               })));
+    assert!(wm.focused() == ws[0]);
+    press(&backend, MouseKind::Press, 30, 6);
+    assert_eq!(wm.next_event(),
+               Some(Event::FocusChanged(ws[2].borrow().id())));
+    assert!(wm.focused() == ws[2]);
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn click_focuses_floating_window_on_top() {
+    let (backend, mut wm) = setup(40, 10);
+    let tiled = wm.focused_window();
+    let popup = wm.new_floating(5, 2, 10, 4);
+    wm.toggle_focus_mode();
+    press(&backend, MouseKind::Press, 6, 3);
+    drain(&mut wm);
+    assert!(wm.focused() == popup);
+    press(&backend, MouseKind::Press, 30, 3);
+    drain(&mut wm);
+    assert!(wm.focused() == tiled);
+}
+
+#[test]
+fn click_selects_tab() {
+    let (backend, mut wm) = setup(40, 6);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    let first = wm.focused();
+    wm.print("first tab");
+    wm.split();
+    assert!(!backend.screen().contains("first tab"));
+    press(&backend, MouseKind::Press, 22, 0);
+    assert_eq!(wm.next_event(),
+               Some(Event::FocusChanged(first.borrow().id())));
+    assert_eq!(wm.next_event(), None);
+    assert!(wm.focused() == first);
+    assert!(backend.screen().contains("first tab"));
+}
+
+#[test]
+fn click_on_popup_over_tabs_focuses_popup() {
+    let (backend, mut wm) = setup(40, 6);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    let second = wm.split().borrow().id();
+    let popup = wm.new_floating(0, 0, 30, 5);
+    let popup_id = popup.borrow().id();
+    wm.focus_window(second).unwrap();
+    // The tab of the first window is under the popup
+    press(&backend, MouseKind::Press, 5, 0);
+    assert_eq!(wm.next_event(), Some(Event::FocusChanged(popup_id)));
+    assert!(wm.focused() == popup);
+    assert_eq!(wm.focus, 1);
+}
+
+#[test]
+fn click_in_fullscreen_does_not_select_hidden_tab() {
+    let (backend, mut wm) = setup(40, 6);
+    wm.set_split_direction(WindowSplitDirection::Tabbed);
+    let second = wm.split();
+    wm.toggle_fullscreen();
+    press(&backend, MouseKind::Press, 5, 0);
+    assert_eq!(wm.next_event(), Some(Event::Mouse(MouseEvent {
+                   x: 5,
+                   y: 0,
+                   kind: MouseKind::Press,
+               })));
+    assert_eq!(wm.focus, 1);
+    wm.toggle_fullscreen();
+    assert!(wm.focused() == second);
+}
+
+#[test]
+fn drag_border_resizes_split() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    press(&backend, MouseKind::Press, 20, 3);
+    press(&backend, MouseKind::Drag, 25, 4);
+    press(&backend, MouseKind::Release, 25, 4);
+    assert_eq!(wm.next_event(), None);
+    assert_eq!(geometry(&wm), vec![(0, 0, 25, 10), (25, 0, 15, 10)]);
+    // Moves after the release are passed on
+    press(&backend, MouseKind::Drag, 30, 4);
+    assert!(wm.next_event().is_some());
+    assert_eq!(geometry(&wm), vec![(0, 0, 25, 10), (25, 0, 15, 10)]);
+}
+
+#[test]
+fn drag_header_resizes_split() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    press(&backend, MouseKind::Press, 3, 4);
+    press(&backend, MouseKind::Drag, 3, 6);
+    drain(&mut wm);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 7), (0, 7, 40, 3)]);
+    press(&backend, MouseKind::Drag, 3, 9);
+    drain(&mut wm);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 8), (0, 8, 40, 2)]);
+}
+
+#[test]
+fn drag_border_of_nested_window_resizes_parent() {
+    let (backend, mut wm) = setup(60, 10);
+    mixed_layout(&mut wm);
+    press(&backend, MouseKind::Press, 20, 7);
+    press(&backend, MouseKind::Drag, 15, 7);
+    drain(&mut wm);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 15, 10), (15, 0, 25, 5), (15, 5, 25, 5), (40, 0, 20, 10)]);
+}
+
+#[test]
+fn focus_moves_across_containers() {
+    let (_backend, mut wm) = setup(60, 10);
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.change_focus(Direction::Up);
+    wm.set_split_direction(WindowSplitDirection::Vertical);
+    wm.split();
+    wm.change_focus(Direction::Down);
+    wm.set_split_direction(WindowSplitDirection::Vertical);
+    wm.split();
+    // Two rows of two windows each, in separate containers
+    let ws = windows(&wm);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 30, 5), (30, 0, 30, 5), (0, 5, 30, 5), (30, 5, 30, 5)]);
+    assert!(wm.focused() == ws[3]);
+    wm.change_focus(Direction::Up);
+    assert!(wm.focused() == ws[1]);
+    wm.change_focus(Direction::Left);
+    assert!(wm.focused() == ws[0]);
+    wm.change_focus(Direction::Down);
+    assert!(wm.focused() == ws[2]);
+    wm.change_focus(Direction::Down);
+    assert!(wm.focused() == ws[2]);
+}
+
+#[test]
+fn focus_prefers_overlapping_neighbour() {
+    let (_backend, mut wm) = setup(60, 12);
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.split();
+    wm.split();
+    let ws = windows(&wm);
+    wm.change_focus(Direction::Left);
+    assert!(wm.focused() == ws[0]);
+    // The middle window is closest to the centre of the left one
+    wm.change_focus(Direction::Right);
+    assert!(wm.focused() == ws[2]);
+    wm.change_focus(Direction::Down);
+    assert!(wm.focused() == ws[3]);
+    // Going left from a deep window in a column leaves the container
+    wm.change_focus(Direction::Left);
+    assert!(wm.focused() == ws[0]);
+}
+
+fn names(wm: &WindowContainer) -> Vec<String> {
+    windows(wm).iter().map(|w| text(&w.borrow()).concat()).collect()
+}
+
+#[test]
+fn move_focused_swaps_siblings() {
+    let (_backend, mut wm) = setup(60, 10);
+    wm.print("a");
+    wm.split();
+    wm.print("b");
+    wm.grow(Dimension::Width, ResizeAmount::Cells(10));
+    let b = wm.focused();
+    wm.split();
+    wm.print("c");
+    let before = geometry(&wm);
+    wm.move_focused(Direction::Left);
+    assert_eq!(names(&wm), vec!["a", "c", "b"]);
+    // The slots keep their sizes
+    assert_eq!(geometry(&wm), before);
+    assert_eq!(text(&wm.focused().borrow()), vec!["c".to_owned()]);
+    wm.move_focused(Direction::Left);
+    wm.move_focused(Direction::Left);
+    assert_eq!(names(&wm), vec!["c", "a", "b"]);
+    assert!(windows(&wm)[2] == b);
+}
+
+#[test]
+fn move_focused_enters_neighbouring_container() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.print("a");
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.print("b1");
+    wm.split();
+    wm.print("b2");
+    wm.change_focus(Direction::Left);
+    wm.move_focused(Direction::Right);
+    assert_eq!(names(&wm), vec!["a", "b1", "b2"]);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 40, 3), (0, 3, 40, 3), (0, 6, 40, 4)]);
+    assert_eq!(text(&wm.focused().borrow()), vec!["a".to_owned()]);
+}
+
+#[test]
+fn move_focused_leaves_container() {
+    let (_backend, mut wm) = setup(60, 10);
+    wm.print("a");
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.print("b1");
+    wm.split();
+    wm.print("b2");
+    wm.move_focused(Direction::Right);
+    assert_eq!(names(&wm), vec!["a", "b1", "b2"]);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 0, 20, 10), (40, 0, 20, 10)]);
+    assert_eq!(wm.focus, 2);
+    // Moving back enters the container, and then leaves it on the other side
+    wm.move_focused(Direction::Left);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 30, 10), (30, 0, 30, 5), (30, 5, 30, 5)]);
+    wm.move_focused(Direction::Left);
+    assert_eq!(names(&wm), vec!["a", "b2", "b1"]);
+    assert_eq!(geometry(&wm),
+               vec![(0, 0, 20, 10), (20, 0, 20, 10), (40, 0, 20, 10)]);
+}
+
+#[test]
+fn move_focused_splits_root_the_other_way() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.print("a");
+    wm.split();
+    wm.print("b");
+    wm.move_focused(Direction::Down);
+    assert_eq!(wm.direction, WindowSplitDirection::Horizontal);
+    assert_eq!(names(&wm), vec!["a", "b"]);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 5), (0, 5, 40, 5)]);
+    // Nothing happens at the edge
+    wm.move_focused(Direction::Down);
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 5), (0, 5, 40, 5)]);
+    // Moving up again enters the container that holds the old tree
+    wm.move_focused(Direction::Up);
+    assert_eq!(names(&wm), vec!["a", "b"]);
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+}
+
+#[test]
+fn swap_with_exchanges_places() {
+    let (backend, mut wm) = setup(60, 10);
+    wm.print("a");
+    let a = wm.focused();
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    wm.print("b1");
+    wm.split();
+    wm.print("b2");
+    let b2 = wm.focused().borrow().id();
+    let before = geometry(&wm);
+    let id = a.borrow().id();
+    wm.focus_window(id).unwrap();
+    wm.swap_with(b2).unwrap();
+    assert_eq!(names(&wm), vec!["b2", "b1", "a"]);
+    assert_eq!(geometry(&wm), before);
+    assert!(wm.focused() == a);
+    let gone = Window::new(backend.clone());
+    match wm.swap_with(gone.id()) {
+        Err(CursesError::WindowNotFound(_)) => {}
+        _ => panic!("swapped with a missing window"),
+    }
+}
+
+#[test]
+fn fullscreen_covers_screen_and_restores_layout() {
+    let (backend, mut wm) = setup(60, 10);
+    wm.print("a");
+    wm.split();
+    wm.print("b");
+    wm.grow(Dimension::Width, ResizeAmount::Cells(6));
+    let before = geometry(&wm);
+    let b = wm.focused();
+    wm.toggle_fullscreen();
+    assert!(wm.is_fullscreen());
+    {
+        let w = b.borrow();
+        assert_eq!((w.x, w.y, w.xmax, w.ymax), (0, 0, 60, 10));
+        assert_eq!(backend.geometry(w.win), Some((0, 0, 60, 9)));
+    }
+    assert!(!backend.screen().contains("a"));
+    // Focus stays on the fullscreen window
+    wm.change_focus(Direction::Left);
+    assert!(wm.focused() == b);
+    wm.toggle_fullscreen();
+    assert!(!wm.is_fullscreen());
+    assert_eq!(geometry(&wm), before);
+    assert!(wm.focused() == b);
+    assert!(backend.screen().contains("a"));
+}
+
+#[test]
+fn tiles_do_not_change_while_fullscreen() {
+    let (_backend, mut wm) = setup(60, 10);
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.split();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(4));
+    let before = geometry(&wm);
+    let focused = wm.focused();
+    wm.toggle_fullscreen();
+    wm.grow(Dimension::Width, ResizeAmount::Cells(6));
+    wm.shrink(Dimension::Width, ResizeAmount::Cells(2));
+    wm.move_focused(Direction::Left);
+    wm.swap_with(first).unwrap();
+    wm.set_layout(WindowSplitDirection::Tabbed);
+    wm.toggle_fullscreen();
+    assert_eq!(geometry(&wm), before);
+    assert!(wm.focused() == focused);
+    // A new window ends fullscreen mode
+    wm.toggle_fullscreen();
+    let new = wm.split();
+    assert!(!wm.is_fullscreen());
+    assert!(wm.focused() == new);
+    assert_eq!(geometry(&wm).iter().map(|g| g.2).sum::<i32>(), 60);
+}
+
+#[test]
+fn fullscreen_key_binding() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.set_bindings(KeyBindings::defaults());
+    wm.split();
+    push_keys(&backend, &[27, b'f' as i32]);
+    assert_eq!(wm.next_event(), None);
+    assert!(wm.is_fullscreen());
+    push_keys(&backend, &[27, b'f' as i32]);
+    assert_eq!(wm.next_event(), None);
+    assert!(!wm.is_fullscreen());
+    assert_eq!(geometry(&wm), vec![(0, 0, 20, 10), (20, 0, 20, 10)]);
+}
+
+#[test]
+fn closing_fullscreen_window_ends_fullscreen() {
+    let (_backend, mut wm) = setup(40, 10);
+    wm.split();
+    wm.toggle_fullscreen();
+    wm.delete();
+    assert!(!wm.is_fullscreen());
+    assert_eq!(geometry(&wm), vec![(0, 0, 40, 10)]);
+}
+
+#[test]
+fn fullscreen_floating_window_returns_to_its_place() {
+    let (_backend, mut wm) = setup(40, 10);
+    let w = wm.new_floating(5, 2, 10, 4);
+    wm.toggle_fullscreen();
+    assert_eq!((w.borrow().xmax, w.borrow().ymax), (40, 10));
+    wm.toggle_fullscreen();
+    let w = w.borrow();
+    assert_eq!((w.x, w.y, w.xmax, w.ymax), (5, 2, 10, 4));
+}
+
+#[test]
+fn new_manager_keeps_theme_of_shared_backend() {
+    let backend = Rc::new(HeadlessBackend::new(40, 10));
+    let mut first = Tcwm::with_backend(backend.clone());
+    let theme = Theme {
+        border: ColorPair::new(ThemeColor::Red, ThemeColor::Default),
+        ..Theme::default()
+    };
+    first.set_theme(theme);
+    let second = Tcwm::with_backend(backend.clone());
+    assert_eq!(backend.theme(), theme);
+    assert_eq!(first.theme(), theme);
+    assert_eq!(second.theme(), theme);
+}
+
+#[test]
+fn set_theme_redraws_screen() {
+    let (backend, mut wm) = setup(40, 10);
+    wm.split();
+    assert_eq!(backend.theme(), Theme::default());
+    let theme = Theme {
+        header_focused: ColorPair::new(ThemeColor::Indexed(208), ThemeColor::Rgb(10, 20, 30)),
+        ..Theme::default()
+    };
+    backend.take_refreshed();
+    wm.set_theme(theme);
+    assert_eq!(backend.theme(), theme);
+    assert_eq!(wm.theme(), theme);
+    let refreshed = backend.take_refreshed();
+    for w in windows(&wm) {
+        let w = w.borrow();
+        assert!(refreshed.contains(&w.win));
+        assert!(refreshed.contains(&w.header_win));
+    }
+    let border = windows(&wm)[1].borrow().border_win.unwrap();
+    assert_eq!(backend.color(border), Some(Color::Border));
+}
+
+#[test]
+fn theme_colors_fall_back_to_palette() {
+    assert_eq!(ThemeColor::Default.palette_index(8), -1);
+    assert_eq!(ThemeColor::Cyan.palette_index(256), 6);
+    assert_eq!(ThemeColor::Indexed(208).palette_index(256), 208);
+    assert_eq!(ThemeColor::Indexed(208).palette_index(8), 3);
+    assert_eq!(ThemeColor::Rgb(255, 135, 0).palette_index(256), 208);
+    assert_eq!(ThemeColor::Rgb(128, 128, 128).palette_index(256), 244);
+    assert_eq!(ThemeColor::Rgb(0, 0, 200).palette_index(8), 4);
+    assert_eq!(Theme::default().pair(Color::Urgent).bg, ThemeColor::Red);
+}
+
+#[test]
+fn urgent_header_unless_focused() {
+    let (backend, mut wm) = setup(40, 10);
+    let first = wm.focused();
+    wm.split();
+    first.borrow_mut().set_urgent(true);
+    let header = first.borrow().header_win;
+    assert_eq!(backend.color(header), Some(Color::Urgent));
+    let id = first.borrow().id();
+    wm.focus_window(id).unwrap();
+    assert_eq!(backend.color(header), Some(Color::StatusSelected));
+    assert!(first.borrow().is_urgent());
+}
+
+#[test]
+fn styled_text_wraps_across_spans() {
+    let bold = Style::new().bold();
+    let text = StyledText::new().span("abc", bold).span("de", Style::new());
+    let rows = text.wrap(2);
+    assert_eq!(rows.iter().map(|r| r.text()).collect::<Vec<_>>(), ["ab", "cd", "e"]);
+    assert_eq!(rows[1].spans.len(), 2);
+    assert_eq!(rows[1].spans[0].style, bold);
+    assert_eq!(StyledText::from("").wrap(5), vec![StyledText::new()]);
+}
+
+#[test]
+fn styles_survive_reprint_after_resize() {
+    let (backend, mut wm) = setup(20, 5);
+    let error = Style::new().fg(ThemeColor::Red).bold();
+    wm.print(StyledText::new().span("[error] ", error).span("disk full", Style::new()));
+    let win = wm.focused_window().borrow().win;
+    {
+        let styles = backend.styles(win).unwrap();
+        assert_eq!(styles[0][0], error);
+        assert_eq!(styles[0][8], Style::default());
+    }
+    // Narrower, so the line wraps and the rows move
+    backend.resize_screen(6, 5);
+    assert!(wm.next_event().is_some());
+    assert_eq!(contents(&backend, &wm), ["[error", "] disk", " full", ""]);
+    let styles = backend.styles(win).unwrap();
+    assert_eq!(styles[0][0], error);
+    assert_eq!(styles[1][0], error);
+    assert_eq!(styles[1][2], Style::default());
+    assert_eq!(styles[2][0], Style::default());
+}
+
+#[test]
+fn wrap_measures_display_width() {
+    assert_eq!(display_width("日本語"), 6);
+    assert_eq!(display_width("e\u{301}te\u{301}"), 3);
+    assert_eq!(display_width("👍🏽"), 2);
+    let rows = |text: &str, width| {
+        StyledText::from(text).wrap(width).iter().map(|r| r.text()).collect::<Vec<_>>()
+    };
+    assert_eq!(rows("日本語テキスト", 5), ["日本", "語テ", "キス", "ト"]);
+    assert_eq!(rows("cafe\u{301}s", 4), ["cafe\u{301}", "s"]);
+}
+
+#[test]
+fn word_wrap_breaks_at_spaces() {
+    let rows = |text: &str, width| {
+        StyledText::from(text).wrap_words(width).iter().map(|r| r.text()).collect::<Vec<_>>()
+    };
+    assert_eq!(rows("the quick brown fox", 10), ["the quick ", "brown fox"]);
+    assert_eq!(rows("a verylongword", 5), ["a ", "veryl", "ongwo", "rd"]);
+    assert_eq!(rows("日本 語", 4), ["日本", "語"]);
+}
+
+#[test]
+fn wide_text_does_not_overwrite_next_lines() {
+    let (backend, mut wm) = setup(7, 5);
+    wm.print("ファイル名.txt");
+    wm.print("äöü");
+    assert_eq!(contents(&backend, &wm), ["ファイ", "ル名.tx", "t", "äöü"]);
+    wm.focused_window().borrow_mut().set_word_wrap(true);
+    wm.print("ab cdefgh");
+    assert_eq!(contents(&backend, &wm), ["t", "äöü", "ab", "cdefgh"]);
+    wm.scroll_up(2);
+    assert_eq!(contents(&backend, &wm), ["ファイ", "ル名.tx", "t", "äöü"]);
+}
+
+#[test]
+fn ansi_sgr_becomes_styles() {
+    let mut parser = AnsiParser::new(true);
+    let lines = parser.feed(concat!("\x1b[1;31mERROR\x1b[0m ok ",
+                                    "\x1b[38;5;208mx\x1b[48;2;1;2;3my\x1b[39;49m\n"));
+    let red = Style::new().fg(ThemeColor::Indexed(1)).bold();
+    let orange = Style::new().fg(ThemeColor::Indexed(208));
+    assert_eq!(lines,
+               vec![StyledText::new()
+                        .span("ERROR", red)
+                        .span(" ok ", Style::new())
+                        .span("x", orange)
+                        .span("y", orange.bg(ThemeColor::Rgb(1, 2, 3)))]);
+    // Sequences split between reads, and an OSC title that is dropped
+    assert!(parser.feed("a\x1b[3").is_empty());
+    let lines = parser.feed("2mb\x1b]0;title\x07c\r\n");
+    assert_eq!(lines,
+               vec![StyledText::new()
+                        .span("a", Style::new())
+                        .span("bc", Style::new().fg(ThemeColor::Indexed(2)))]);
+}
+
+#[test]
+fn ansi_carriage_return_and_clear_line() {
+    let mut parser = AnsiParser::new(true);
+    assert!(parser.feed("progress 10%").is_empty());
+    assert!(parser.feed("\rprogress 100%").is_empty());
+    assert_eq!(parser.line().text(), "progress 100%");
+    parser.feed("\rdone\x1b[K");
+    assert_eq!(parser.line().text(), "done");
+    parser.feed("\x1b[2K\rnew\x1b[2Dw");
+    assert_eq!(parser.line().text(), "nww");
+    assert_eq!(parser.feed("\n")[0].text(), "nww");
+}
+
+#[test]
+fn ansi_cursor_counts_display_columns() {
+    let mut parser = AnsiParser::new(true);
+    parser.feed("中文ab\x1b[3Gx");
+    assert_eq!(parser.line().text(), "中x ab");
+    parser.feed("\r\x1b[5Cy\x1b[Kz");
+    assert_eq!(parser.line().text(), "中x ayz");
+    parser.clear_line();
+    parser.feed("中\u{301}\tx");
+    assert_eq!(parser.line().text(), "中\u{301}      x");
+    assert_eq!(parser.line().width(), 9);
+    // Overwriting the right half of a wide character erases it
+    parser.feed("\r\x1b[1Co");
+    assert_eq!(parser.line().text(), " o      x");
+}
+
+#[test]
+fn print_after_partial_write_starts_new_line() {
+    let (_backend, wm) = setup(30, 8);
+    let w = wm.focused_window();
+    let mut w = w.borrow_mut();
+    w.write("abc");
+    w.print("xyz");
+    w.write("def\n");
+    assert_eq!(text(&w), ["abc", "xyz", "def"]);
+    w.set_ansi(true);
+    w.write("\x1b[1mpartial");
+    w.print("line");
+    w.write("\rnext\n");
+    assert_eq!(text(&w)[3..], ["partial", "line", "next"]);
+}
+
+#[test]
+fn styled_print_keeps_styles_with_ansi() {
+    let (_backend, wm) = setup(30, 5);
+    let w = wm.focused_window();
+    let mut w = w.borrow_mut();
+    w.set_ansi(true);
+    w.print(StyledText::new().span("bold", Style::new().bold()).span(" plain", Style::new()));
+    assert_eq!(text(&w), ["bold plain"]);
+    assert!(w.lines[0].spans[0].style.bold);
+    assert!(!w.lines[0].spans[1].style.bold);
+}
+
+#[test]
+fn ansi_window_output() {
+    let (backend, wm) = setup(30, 5);
+    let w = wm.focused_window();
+    let mut w = w.borrow_mut();
+    w.set_ansi(true);
+    w.print("\x1b[32mgreen\x1b[0m");
+    w.write("loading 1/2");
+    assert_eq!(text(&w), ["green", "loading 1/2"]);
+    w.write("\rloading 2/2");
+    assert_eq!(text(&w), ["green", "loading 2/2"]);
+    w.write("\r\x1b[Kfinished\nnext");
+    assert_eq!(text(&w), ["green", "finished", "next"]);
+    assert_eq!(w.lines[0].spans[0].style.fg, Some(ThemeColor::Indexed(2)));
+    assert_eq!(backend.contents(w.win).unwrap()[1].trim_end(), "finished");
+    // Without the interpreter the escape character is dropped
+    w.set_ansi(false);
+    w.print("\x1b[1mraw");
+    assert_eq!(text(&w).last().unwrap(), "\x1b[1mraw");
+    w.write("\n\x1b[1mraw\n");
+    assert_eq!(text(&w).last().unwrap(), "[1mraw");
+}
+
+/// Polls terminal panes until `done` holds or a few seconds have passed.
+fn wait_for<F>(wm: &mut Tcwm, mut done: F)
+    where F: FnMut(&Tcwm) -> bool
+{
+    for _ in 0..300 {
+        wm.next_event();
+        if done(wm) {
+            return;
+        }
+        ::std::thread::sleep(::std::time::Duration::from_millis(10));
+    }
+    panic!("Timed out waiting for terminal output");
+}
+
+#[test]
+fn terminal_shows_program_output() {
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    let id = wm.open_terminal(Command::new("sh").args(["-c", "printf '\\033[31mred\\033[0m\\n'"]))
+        .unwrap();
+    let w = wm.focused_window();
+    assert_eq!(w.borrow().id, id);
+    assert!(w.borrow().is_terminal());
+    wait_for(&mut wm, |_| w.borrow().exit_status().is_some());
+    let w = w.borrow();
+    assert!(w.exit_status().unwrap().success());
+    assert_eq!(text(&w)[0], "red");
+    assert_eq!(w.lines[0].spans[0].style.fg, Some(ThemeColor::Indexed(1)));
+    assert!(w.header.starts_with("sh -c"));
+    assert!(w.header.ends_with("(exit status: 0)"));
+}
+
+#[test]
+fn terminal_receives_keys() {
+    use std::process::Command;
+    let (backend, mut wm) = setup(40, 10);
+    wm.open_terminal(&mut Command::new("cat")).unwrap();
+    let w = wm.focused_window();
+    push_keys(&backend, &['h' as i32, 'i' as i32, 10]);
+    wait_for(&mut wm, |_| text(&w.borrow()).iter().filter(|l| l.as_str() == "hi").count() == 2);
+    assert_eq!(w.borrow().exit_status(), None);
+    // Ctrl-D ends the input of cat
+    backend.push_key(4);
+    wait_for(&mut wm, |_| w.borrow().exit_status().is_some());
+}
+
+#[test]
+fn terminal_follows_window_size() {
+    use std::process::Command;
+    let (backend, mut wm) = setup(40, 10);
+    wm.open_terminal(Command::new("sh").args(["-c", "read x; stty size"])).unwrap();
+    let w = wm.focused_window();
+    let (width, height) = (w.borrow().content_width(), w.borrow().content_height());
+    backend.resize_screen(60, 12);
+    wm.next_event();
+    assert!(w.borrow().content_width() > width);
+    let (width, height2) = (w.borrow().content_width(), w.borrow().content_height());
+    assert!(height2 > height);
+    push_keys(&backend, &[10]);
+    let size = format!("{} {}", height2, width);
+    wait_for(&mut wm, |_| text(&w.borrow()).contains(&size));
+}
+
+#[test]
+fn failing_terminal_closes_its_window() {
+    use std::error::Error;
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    match wm.open_terminal(&mut Command::new("/nonexistent/program")) {
+        Err(err @ CursesError::Io(_)) => assert!(err.source().is_some()),
+        _ => panic!("Expected an error"),
+    }
+    assert_eq!(windows(&wm).len(), 1);
+}
+
+#[test]
+fn command_output_is_streamed_into_window() {
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    let id = wm.run_command(Command::new("sh").args(["-c", "echo out; echo err >&2; exit 3"]))
+        .unwrap();
+    let w = wm.focused_window();
+    assert_eq!(w.borrow().id, id);
+    assert_eq!(w.borrow().header, "sh -c echo out; echo err >&2; exit 3");
+    wait_for(&mut wm, |_| w.borrow().exit_status().is_some());
+    let w = w.borrow();
+    let mut lines = text(&w);
+    lines.sort();
+    assert_eq!(lines, ["err", "out"]);
+    assert_eq!(w.exit_status().unwrap().code(), Some(3));
+    assert!(w.header.ends_with("(exit status: 3)"));
+}
+
+#[test]
+fn command_output_keeps_stdout_and_stderr_lines_apart() {
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    let script = "printf 'out-start '; echo 'err line' >&2; sleep 0.1; echo out-end; printf last";
+    wm.run_command(Command::new("sh").args(["-c", script])).unwrap();
+    let w = wm.focused_window();
+    wait_for(&mut wm, |_| w.borrow().exit_status().is_some());
+    assert_eq!(text(&w.borrow()), ["err line", "out-start out-end", "last"]);
+}
+
+#[test]
+fn running_command_does_not_block_input() {
+    use std::process::Command;
+    let (backend, mut wm) = setup(40, 10);
+    wm.run_command(Command::new("sh").args(["-c", "echo started; sleep 5; echo done"]))
+        .unwrap();
+    let w = wm.focused_window();
+    wait_for(&mut wm, |_| text(&w.borrow()) == ["started"]);
+    backend.push_key('q' as i32);
+    assert_eq!(wm.next_event(), Some(Event::Key(Key::Char('q'))));
+    assert_eq!(w.borrow().exit_status(), None);
+    // Closing the window stops the command
+    let id = w.borrow().id;
+    wm.close_window(id).unwrap();
+}
+
+#[test]
+fn failing_command_closes_its_window() {
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    assert!(wm.run_command(&mut Command::new("/nonexistent/program")).is_err());
+    assert_eq!(windows(&wm).len(), 1);
+}
+
+#[test]
+fn sender_queues_changes_from_other_threads() {
+    let (_backend, mut wm) = setup(40, 10);
+    let first = wm.focused_window().borrow().id;
+    let second = wm.split().borrow().id;
+    let sender = wm.sender();
+    let worker = sender.clone();
+    ::std::thread::spawn(move || {
+            worker.print(first, "from a thread");
+            worker.set_header(first, "worker");
+            worker.write(second, "partial");
+            worker.close(second);
+            worker.print(second, "dropped");
+        })
+        .join()
+        .unwrap();
+    // Nothing changes until the event loop runs
+    assert!(text(&wm.window(first).unwrap().borrow()).is_empty());
+    assert_eq!(wm.next_event(), Some(Event::WindowClosed(second)));
+    let w = wm.window(first).unwrap();
+    assert_eq!(text(&w.borrow()), ["from a thread"]);
+    assert_eq!(w.borrow().header, "worker");
+    assert!(wm.window(second).is_err());
+    drop(wm);
+    assert!(!sender.print(first, "gone"));
+}
+
+/// Checks that no container is empty or holds nothing but another container.
+fn assert_normalized(c: &WindowContainer) {
+    assert!(c.focus < c.payload.len());
+    for pl in c.payload.iter() {
+        if let WindowPayload::Container(ref inner) = *pl {
+            let inner = inner.borrow();
+            assert!(!inner.payload.is_empty());
+            assert!(inner.payload.len() > 1 || inner.payload[0].is_window());
+            assert_normalized(&inner);
+        }
+    }
+}
+
+#[test]
+fn moves_to_the_edge_do_not_nest_containers() {
+    let (_backend, mut wm) = setup(60, 20);
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.move_focused(Direction::Up);
+    wm.move_focused(Direction::Left);
+    wm.move_focused(Direction::Down);
+    wm.move_focused(Direction::Right);
+    assert_normalized(&wm);
+    wm.swap_with(first).unwrap();
+    wm.delete();
+    assert_normalized(&wm);
+    assert_eq!(geometry(&wm), vec![(0, 0, 60, 20)]);
+}
+
+#[test]
+fn moves_after_closing_nested_windows() {
+    let (_backend, mut wm) = setup(60, 20);
+    wm.split();
+    wm.split();
+    wm.move_focused(Direction::Right);
+    wm.delete();
+    wm.move_focused(Direction::Down);
+    let id = wm.split().borrow().id();
+    wm.close_window(id).unwrap();
+    assert_normalized(&wm);
+    wm.set_layout(WindowSplitDirection::Vertical);
+
+    let first = wm.focused_window().borrow().id();
+    wm.split();
+    wm.set_split_direction(WindowSplitDirection::Horizontal);
+    let nested = wm.split().borrow().id();
+    wm.split();
+    wm.focus_window(nested).unwrap();
+    wm.move_focused(Direction::Down);
+    wm.close_window(first).unwrap();
+    wm.split();
+    wm.move_focused(Direction::Right);
+    wm.delete();
+    wm.move_to_workspace("2");
+    wm.move_focused(Direction::Up);
+    wm.move_focused(Direction::Left);
+    assert_normalized(&wm);
+    wm.grow(Dimension::Width, ResizeAmount::Cells(2));
+    while windows(&wm).len() > 1 {
+        wm.delete();
+        assert_normalized(&wm);
+    }
+}
+
+#[test]
+fn layout_round_trips_after_moves_and_closes() {
+    let (_backend, mut wm) = setup(60, 20);
+    wm.split();
+    wm.move_focused(Direction::Down);
+    wm.move_focused(Direction::Left);
+    let id = wm.split().borrow().id();
+    wm.split();
+    wm.close_window(id).unwrap();
+    let saved = wm.save_layout(false);
+    let (_backend, mut other) = setup(60, 20);
+    other.restore_layout(&Layout::from_json(&saved.to_json()).unwrap()).unwrap();
+    assert_eq!(geometry(&other), geometry(&wm));
+}
+
+#[test]
+fn terminal_ends_while_other_programs_run() {
+    use std::process::Command;
+    let (_backend, mut wm) = setup(40, 10);
+    let mut command = Command::new("true");
+    wm.open_terminal(&mut command).unwrap();
+    let w = wm.focused_window();
+    // A program started later does not hold the pty open
+    wm.run_command(Command::new("sleep").arg("5")).unwrap();
+    wait_for(&mut wm, |_| w.borrow().exit_status().is_some());
+}
@@ -0,0 +1,180 @@
+//! Styled text.
+//!
+//! Window buffers hold `StyledText`, a line made of spans that each have their
+//! own colors and attributes. Plain strings convert into text with a single
+//! unstyled span, so `print("...")` keeps working.
+//!
+//! Text is measured in terminal cells per grapheme cluster, so combining marks
+//! take no space of their own and CJK characters and emoji take two cells.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use ThemeColor;
+
+/// Number of terminal cells `s` takes.
+pub fn display_width(s: &str) -> usize {
+    s.graphemes(true).map(grapheme_width).sum()
+}
+
+fn grapheme_width(g: &str) -> usize {
+    // Sequences such as emoji with modifiers are drawn as a single glyph
+    g.width().min(2)
+}
+
+/// Colors and attributes of a span. Colors that are `None` come from the
+/// `text` colors of the theme.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub fg: Option<ThemeColor>,
+    pub bg: Option<ThemeColor>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+impl Style {
+    pub fn new() -> Style {
+        Style::default()
+    }
+    pub fn fg(mut self, color: ThemeColor) -> Style {
+        self.fg = Some(color);
+        self
+    }
+    pub fn bg(mut self, color: ThemeColor) -> Style {
+        self.bg = Some(color);
+        self
+    }
+    pub fn bold(mut self) -> Style {
+        self.bold = true;
+        self
+    }
+    pub fn dim(mut self) -> Style {
+        self.dim = true;
+        self
+    }
+    pub fn italic(mut self) -> Style {
+        self.italic = true;
+        self
+    }
+    pub fn underline(mut self) -> Style {
+        self.underline = true;
+        self
+    }
+    pub fn reverse(mut self) -> Style {
+        self.reverse = true;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// A line of text made of styled spans.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StyledText {
+    pub spans: Vec<Span>,
+}
+impl StyledText {
+    pub fn new() -> StyledText {
+        StyledText::default()
+    }
+    /// Appends a span, merging it into the last one if the styles match.
+    pub fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(last) = self.spans.last_mut() {
+            if last.style == style {
+                last.text.push_str(text);
+                return;
+            }
+        }
+        self.spans.push(Span {
+            text: text.to_owned(),
+            style,
+        });
+    }
+    /// Builder version of `push`.
+    pub fn span(mut self, text: &str, style: Style) -> StyledText {
+        self.push(text, style);
+        self
+    }
+    /// The text without styles.
+    pub fn text(&self) -> String {
+        self.spans.iter().map(|s| &s.text[..]).collect()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+    /// Number of terminal cells the text takes.
+    pub fn width(&self) -> usize {
+        self.spans.iter().map(|s| display_width(&s.text)).sum()
+    }
+    /// Splits the text into rows at most `width` cells wide. A character that
+    /// does not fit on a row starts the next one.
+    pub fn wrap(&self, width: usize) -> Vec<StyledText> {
+        self.wrap_rows(width, false)
+    }
+    /// Like `wrap`, but breaks rows at whitespace when possible. Words longer
+    /// than a row are split.
+    pub fn wrap_words(&self, width: usize) -> Vec<StyledText> {
+        self.wrap_rows(width, true)
+    }
+    fn wrap_rows(&self, width: usize, words: bool) -> Vec<StyledText> {
+        let width = width.max(1);
+        let is_space = |g: &str| g.chars().all(char::is_whitespace);
+        let mut rows: Vec<Vec<(&str, Style)>> = vec![vec![]];
+        let mut len = 0;
+        for span in self.spans.iter() {
+            for g in span.text.graphemes(true) {
+                let w = grapheme_width(g);
+                if len + w > width && len > 0 {
+                    let mut next = vec![];
+                    if words && !is_space(g) {
+                        // The word that does not fit moves to the next row
+                        let row = rows.last_mut().unwrap();
+                        if let Some(i) = row.iter().rposition(|&(g, _)| is_space(g)) {
+                            next = row.split_off(i + 1);
+                        }
+                    }
+                    len = next.iter().map(|&(g, _)| grapheme_width(g)).sum();
+                    rows.push(next);
+                    if words && is_space(g) {
+                        continue;
+                    }
+                }
+                rows.last_mut().unwrap().push((g, span.style));
+                len += w;
+            }
+        }
+        rows.into_iter()
+            .map(|row| {
+                let mut text = StyledText::new();
+                for (g, style) in row {
+                    text.push(g, style);
+                }
+                text
+            })
+            .collect()
+    }
+}
+impl<'a> From<&'a str> for StyledText {
+    fn from(s: &'a str) -> StyledText {
+        StyledText::new().span(s, Style::default())
+    }
+}
+impl<'a> From<&'a String> for StyledText {
+    fn from(s: &'a String) -> StyledText {
+        StyledText::from(&s[..])
+    }
+}
+impl From<String> for StyledText {
+    fn from(s: String) -> StyledText {
+        StyledText::from(&s[..])
+    }
+}
@@ -0,0 +1,179 @@
+//! Colors used to draw windows.
+//!
+//! A `Theme` gives the foreground and background of every kind of surface
+//! tcwm draws. Colors can be one of the eight basic terminal colors, an index
+//! into the 256-color palette or an RGB value. Backends show colors the
+//! terminal does not support as the nearest one it does.
+//!
+//! On terminals that can change their palette, the ncurses backend shows RGB
+//! values exactly by setting entries at the end of the palette to them, and
+//! sets the entries back when it is dropped. Programs in terminal panes that
+//! use those entries show the new colors in the meantime.
+
+use Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ThemeColor {
+    /// The default color of the terminal.
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    /// An entry of the 256-color palette.
+    Indexed(u8),
+    /// Shown exactly if the terminal can change its palette, otherwise as the
+    /// nearest entry of it.
+    Rgb(u8, u8, u8),
+}
+
+/// Foreground and background colors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColorPair {
+    pub fg: ThemeColor,
+    pub bg: ThemeColor,
+}
+impl ColorPair {
+    pub fn new(fg: ThemeColor, bg: ThemeColor) -> ColorPair {
+        ColorPair { fg, bg }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Theme {
+    /// Window contents.
+    pub text: ColorPair,
+    pub header: ColorPair,
+    /// The header of the focused window, and the selected tab or title row.
+    pub header_focused: ColorPair,
+    /// The separator line to the left of a window.
+    pub border: ColorPair,
+    /// The header of a window marked with `Window::set_urgent`.
+    pub urgent: ColorPair,
+}
+impl Theme {
+    /// The colors used for `color`.
+    pub fn pair(&self, color: Color) -> ColorPair {
+        match color {
+            Color::Default | Color::Selection => self.text,
+            Color::Status => self.header,
+            Color::StatusSelected => self.header_focused,
+            Color::Border => self.border,
+            Color::Urgent => self.urgent,
+        }
+    }
+}
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            text: ColorPair::new(ThemeColor::Green, ThemeColor::Black),
+            header: ColorPair::new(ThemeColor::White, ThemeColor::Blue),
+            header_focused: ColorPair::new(ThemeColor::Black, ThemeColor::Cyan),
+            border: ColorPair::new(ThemeColor::Green, ThemeColor::Black),
+            urgent: ColorPair::new(ThemeColor::White, ThemeColor::Red),
+        }
+    }
+}
+
+impl ThemeColor {
+    /// The red, green and blue parts of the color, or `None` for the default
+    /// color. Basic colors use the values of the xterm palette.
+    pub fn rgb(self) -> Option<(u8, u8, u8)> {
+        const BASIC: [(u8, u8, u8); 16] = [(0, 0, 0),
+                                           (205, 0, 0),
+                                           (0, 205, 0),
+                                           (205, 205, 0),
+                                           (0, 0, 238),
+                                           (205, 0, 205),
+                                           (0, 205, 205),
+                                           (229, 229, 229),
+                                           (127, 127, 127),
+                                           (255, 0, 0),
+                                           (0, 255, 0),
+                                           (255, 255, 0),
+                                           (92, 92, 255),
+                                           (255, 0, 255),
+                                           (0, 255, 255),
+                                           (255, 255, 255)];
+        const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+        match self {
+            ThemeColor::Default => None,
+            ThemeColor::Rgb(r, g, b) => Some((r, g, b)),
+            ThemeColor::Indexed(i) if i < 16 => Some(BASIC[i as usize]),
+            ThemeColor::Indexed(i) if i < 232 => {
+                let i = i - 16;
+                Some((CUBE[(i / 36) as usize], CUBE[(i / 6 % 6) as usize], CUBE[(i % 6) as usize]))
+            }
+            ThemeColor::Indexed(i) => {
+                let level = 8 + (i - 232) * 10;
+                Some((level, level, level))
+            }
+            basic => Some(BASIC[basic.basic_index().unwrap() as usize]),
+        }
+    }
+    /// Index of the color in a palette of `colors` entries. The default color
+    /// is -1.
+    pub fn palette_index(self, colors: i32) -> i16 {
+        if let Some(i) = self.basic_index() {
+            return i;
+        }
+        if let ThemeColor::Indexed(i) = self {
+            if (i as i32) < colors {
+                return i as i16;
+            }
+        }
+        let (r, g, b) = match self.rgb() {
+            Some(rgb) => rgb,
+            None => return -1,
+        };
+        if colors >= 256 {
+            nearest_256(r, g, b)
+        } else {
+            // Red, green and blue are bits 0, 1 and 2 of the basic colors
+            let bit = |c: u8, n: i16| if c >= 128 { 1 << n } else { 0 };
+            bit(r, 0) | bit(g, 1) | bit(b, 2)
+        }
+    }
+    fn basic_index(self) -> Option<i16> {
+        match self {
+            ThemeColor::Black => Some(0),
+            ThemeColor::Red => Some(1),
+            ThemeColor::Green => Some(2),
+            ThemeColor::Yellow => Some(3),
+            ThemeColor::Blue => Some(4),
+            ThemeColor::Magenta => Some(5),
+            ThemeColor::Cyan => Some(6),
+            ThemeColor::White => Some(7),
+            _ => None,
+        }
+    }
+}
+
+/// The closest entry in the color cube or the gray ramp of the 256-color
+/// palette.
+fn nearest_256(r: u8, g: u8, b: u8) -> i16 {
+    let step = |c: u8| if c < 48 {
+        0
+    } else if c < 115 {
+        1
+    } else {
+        (c as i16 - 35) / 40
+    };
+    let cube = 16 + 36 * step(r) + 6 * step(g) + step(b);
+    let average = (r as i32 + g as i32 + b as i32) / 3;
+    let gray = 232 + ((average - 8).max(0) / 10).min(23) as i16;
+    let distance = |i: i16| {
+        let (cr, cg, cb) = ThemeColor::Indexed(i as u8).rgb().unwrap();
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, cr) + d(g, cg) + d(b, cb)
+    };
+    if distance(gray) < distance(cube) {
+        gray
+    } else {
+        cube
+    }
+}